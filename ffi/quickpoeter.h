@@ -0,0 +1,79 @@
+/*
+ * C interface of quickpoeter, the advanced rhymes finder
+ * (build with `cargo rustc --lib --release --features ffi --crate-type cdylib`,
+ * link with -lquickpoeter).
+ *
+ * Ownership:
+ *   every qp_collector_new() is paired with qp_collector_free(),
+ *   every non-NULL qp_find() is paired with qp_results_free();
+ *   strings passed to the library are only read during the call and stay yours;
+ *   pointers returned are owned by the library: results and their words live
+ *   until qp_results_free(), qp_last_error() until the next call in the same thread.
+ * A collector may be used from several threads at once, errors are per thread.
+ * Strings are UTF-8, words use ' after the stressed vowel («ко'т»).
+ */
+
+#ifndef QUICKPOETER_H
+#define QUICKPOETER_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define QP_ABI_VERSION 1
+
+typedef struct QpCollector QpCollector;
+typedef struct QpResults QpResults;
+
+/* one found word with the parts of its distance (less is better) */
+typedef struct QpResult {
+    const char *word;
+    float dist;
+    float misc;
+    float vowel;
+    float cons;
+    float structure;
+    float meaning;
+    float popular;
+    float unsymm; /* is not a part of dist */
+    float same_part;
+} QpResult;
+
+/* QP_ABI_VERSION of the library */
+int qp_abi_version(void);
+
+/* Loads the dictionary from dir/res and settings from dir/config ("." if dir is NULL),
+ * profile is a name from config/profiles or NULL. NULL on error. */
+QpCollector *qp_collector_new(const char *dir, const char *profile);
+
+/* NULL is ignored */
+void qp_collector_free(QpCollector *collector);
+
+/* Finds top_n rhymes for query; theme and rps (parts of speech to remove separated
+ * with "+", e.g. "г+н") may be NULL; settings are n_settings overrides like
+ * "stresses.weight=80" for this search only (may be NULL if n_settings is 0).
+ * NULL on error. */
+QpResults *qp_find(const QpCollector *collector, const char *query, const char *theme,
+                   const char *rps, uint32_t top_n, const char *const *settings,
+                   size_t n_settings);
+
+/* 0 for NULL */
+size_t qp_results_len(const QpResults *results);
+
+/* index-th result, the best is the first; NULL if out of range */
+const QpResult *qp_results_get(const QpResults *results, size_t index);
+
+/* NULL is ignored */
+void qp_results_free(QpResults *results);
+
+/* message of the last error in this thread, NULL if the last call succeeded */
+const char *qp_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
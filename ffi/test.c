@@ -0,0 +1,50 @@
+/*
+ * Example and test of the C interface:
+ *   cargo rustc --lib --features ffi --crate-type cdylib
+ *   cc ffi/test.c -Iffi -Ltarget/debug -lquickpoeter -o qp_test
+ *   LD_LIBRARY_PATH=target/debug ./qp_test . ко'т
+ * prints the best rhymes with the parts of distance, exits with 1 on errors
+ */
+
+#include <stdio.h>
+
+#include "quickpoeter.h"
+
+static int fail(const char *what) {
+    const char *error = qp_last_error();
+    fprintf(stderr, "%s: %s\n", what, error ? error : "no error message");
+    return 1;
+}
+
+int main(int argc, char **argv) {
+    const char *dir = argc > 1 ? argv[1] : ".";
+    const char *query = argc > 2 ? argv[2] : "ко'т";
+
+    if (qp_abi_version() != QP_ABI_VERSION) {
+        fprintf(stderr, "ABI version %d, header is for %d\n", qp_abi_version(), QP_ABI_VERSION);
+        return 1;
+    }
+
+    QpCollector *collector = qp_collector_new(dir, NULL);
+    if (!collector) return fail("Can't load the dictionary");
+
+    const char *settings[] = {"stresses.weight=80"};
+    QpResults *results = qp_find(collector, query, NULL, NULL, 10, settings, 1);
+    if (!results) {
+        qp_collector_free(collector);
+        return fail("Can't find");
+    }
+    for (size_t i = 0; i < qp_results_len(results); i++) {
+        const QpResult *r = qp_results_get(results, i);
+        printf("%-20s %8.2f  vowel %.2f  cons %.2f  struct %.2f\n", r->word, r->dist, r->vowel,
+               r->cons, r->structure);
+    }
+    int status = qp_results_get(results, qp_results_len(results)) == NULL ? 0 : 1;
+    qp_results_free(results);
+
+    /* errors are reported, not crashed on */
+    if (qp_find(collector, NULL, NULL, NULL, 10, NULL, 0) || !qp_last_error()) status = 1;
+
+    qp_collector_free(collector);
+    return status;
+}
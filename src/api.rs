@@ -19,22 +19,93 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 This module provides commands for using tool from extern sources (or console)
 */
 
+use std::fmt::{Display, Formatter};
 use std::ops::Deref;
+use std::path::{Path, PathBuf};
 
-use crate::finder::{FindingInfo, WordCollector, WordDistanceResult};
+use crate::evaluator::{evaluate, EvaluationReport, GoldenSet};
+use crate::finder::{FindingInfo, SearchSession, WordCollector, WordDistanceResult};
 use crate::meaner::MeanTheme;
-use crate::reader::{GeneralSettings, MeanStrThemes};
-use crate::translator_ru::ALL_VOWELS;
-use crate::translator_struct::Word;
-use clap::Parser;
+use crate::normalizer::normalize;
+use crate::output::{render, OutputFormat};
+use crate::pattern::{is_pattern, Pattern};
+use crate::reader::{yaml_read, GeneralSettings, MeanStrThemes};
+use crate::sound_play::SoundPlay;
+use crate::syllabifier::hyphenate;
+use crate::translator_ru::{remove_yo, PhoneticAlphabet, ALL_VOWELS};
+use crate::translator_struct::{phones2string, Word};
+use crate::tuner::{RawTuningSet, Tuner, TuningSet, DEFAULT_TUNED};
+use crate::wordplay::{WordPlay, WordPlayResult};
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use serde_json::Value;
 
 /// Compex tool for finding ryphms;
+/// without a command finds rhymes («quickpoeter сло'во» is the same as «quickpoeter find сло'во»)
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
+#[clap(subcommand_negates_reqs = true)]
 pub struct Args {
-    /// What to find (use ' to mind the stress)
-    #[clap(value_parser)]
-    pub to_find: String,
+    #[clap(flatten)]
+    pub find: FindArgs,
+
+    /// Directory with config and res folders
+    #[clap(long, value_parser, global = true, default_value = ".")]
+    pub dir: PathBuf,
+
+    /// Settings profile put over config/coefficients.yaml (one from config/profiles)
+    #[clap(short, long, value_parser, global = true)]
+    pub profile: Option<String>,
+
+    /// Override single setting, e.g. «--set stresses.weight=80» (may be repeated)
+    #[clap(short, long, value_parser, global = true)]
+    pub set: Vec<String>,
+
+    /// Print results as records for scripts instead of the human-readable output
+    #[clap(long, value_enum, global = true)]
+    pub format: Option<OutputFormat>,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Find rhymes, words for sound play or word play
+    Find(FindArgs),
+    /// Find for many queries from a file or stdin (one per line), loading the dictionary once
+    Batch(BatchArgs),
+    /// Interactive session with the dictionary loaded once (type «help» inside)
+    Repl(ReplArgs),
+    /// Local HTTP/JSON server with the dictionary loaded once (see the serve module for endpoints)
+    Serve(ServeArgs),
+    /// JSON-RPC 2.0 over stdin/stdout for editors (see the rpc module for methods)
+    Rpc(RpcArgs),
+    /// Measure distance between two words with all subdistances (primarly for debug purposes)
+    Measure(MeasureArgs),
+    /// Print transcription of words
+    Transcribe(TranscribeArgs),
+    /// Put stress marks using the dictionary
+    Stress(StressArgs),
+    /// Meaning themes from config/themes.yaml
+    #[clap(subcommand)]
+    Themes(ThemesCommand),
+    /// Look into the dictionary
+    #[clap(subcommand)]
+    Dict(DictCommand),
+    /// Show everything known about the word: transcription, syllables, stress, clausula
+    Analyze(AnalyzeArgs),
+    /// Tune settings weights from labelled rhyme pairs and print the resulting profile
+    Tune(TuneArgs),
+    /// Evaluate search quality on golden rhymes (precision@k, MRR, nDCG)
+    Evaluate(EvaluateArgs),
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct FindArgs {
+    /// What to find (use ' to mind the stress) or a pattern like «+!ко pos=с»
+    #[clap(value_parser, required = true)]
+    pub to_find: Option<String>,
 
     /// Mean theme name (one from config/themes.yaml)
     #[clap(short, long, value_parser)]
@@ -61,7 +132,7 @@ pub struct Args {
     #[clap(short, long, value_parser, verbatim_doc_comment)]
     pub rps: Option<String>,
 
-    /// Number of returned best matches (doesn't affect speed)
+    /// Number of returned best matches (all words are scored anyway; to page through them use «more» in repl)
     #[clap(short = 'n', long, value_parser, default_value_t = 100)]
     pub top_n: u32,
 
@@ -69,50 +140,316 @@ pub struct Args {
     #[clap(short, long, value_parser, default_value_t = false)]
     pub debug: bool,
 
-    /// Measure distance to given word (primarly for debug purposes)
+    /// Show transcription next to each rhyme (to check why words are considered similar)
+    #[clap(long, value_enum)]
+    pub notation: Option<PhoneticAlphabet>,
+
+    /// Print rhymes with е instead of ё
+    #[clap(long, value_parser, default_value_t = false)]
+    pub no_yo: bool,
+
+    /// Split every rhyme into syllables («ла-ско-вый»)
+    #[clap(long, value_parser, default_value_t = false)]
+    pub hyphenate: bool,
+
+    /// Find words for sound play inside lines instead of rhymes
+    #[clap(long, value_enum)]
+    pub sound_play: Option<SoundPlay>,
+
+    /// Find anagrams, paronyms or words sounding like the given ones (e.g. «за бор») instead of rhymes
+    #[clap(long, value_enum)]
+    pub word_play: Option<WordPlay>,
+}
+
+impl FindArgs {
+    /// rhymes with the default options
+    pub fn new(to_find: &str) -> Self {
+        FindArgs {
+            to_find: Some(to_find.to_string()),
+            theme: None,
+            rps: None,
+            top_n: 100,
+            debug: false,
+            notation: None,
+            no_yo: false,
+            hyphenate: false,
+            sound_play: None,
+            word_play: None,
+        }
+    }
+}
+
+/// options are the same as for `find` and are defaults for every line
+#[derive(clap::Args, Debug)]
+pub struct BatchArgs {
+    /// File with queries: «сло'во theme=Love rps=г+н top_n=10» (options are optional),
+    /// empty lines and lines starting with # are skipped; stdin if not set or «-»
+    #[clap(value_parser, verbatim_doc_comment)]
+    pub input: Option<PathBuf>,
+
+    /// Number of queries processed in parallel (the order of output is kept)
+    #[clap(short, long, value_parser, default_value_t = 1)]
+    pub jobs: usize,
+
+    /// Mean theme name (one from config/themes.yaml)
+    #[clap(short, long, value_parser)]
+    pub theme: Option<String>,
+
+    /// Remove some parts of speech separated with "+"
     #[clap(short, long, value_parser)]
-    pub measure: Option<String>,
+    pub rps: Option<String>,
+
+    /// Number of returned best matches
+    #[clap(short = 'n', long, value_parser, default_value_t = 100)]
+    pub top_n: u32,
+
+    /// Show transcription next to each rhyme
+    #[clap(long, value_enum)]
+    pub notation: Option<PhoneticAlphabet>,
+
+    /// Print rhymes with е instead of ё
+    #[clap(long, value_parser, default_value_t = false)]
+    pub no_yo: bool,
+
+    /// Split every rhyme into syllables
+    #[clap(long, value_parser, default_value_t = false)]
+    pub hyphenate: bool,
+
+    /// Find words for sound play inside lines instead of rhymes
+    #[clap(long, value_enum)]
+    pub sound_play: Option<SoundPlay>,
+
+    /// Find anagrams, paronyms or words sounding like the given ones instead of rhymes
+    #[clap(long, value_enum)]
+    pub word_play: Option<WordPlay>,
 }
 
+#[derive(clap::Args, Debug)]
+pub struct ReplArgs {
+    /// Number of words on a page (`more` shows the next one)
+    #[clap(short = 'n', long, value_parser, default_value_t = 20)]
+    pub page: u32,
+
+    /// File to keep the history of commands in
+    #[clap(long, value_parser)]
+    pub history: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ServeArgs {
+    /// Address to listen on (only local by default)
+    #[clap(long, value_parser, default_value = "127.0.0.1:8080")]
+    pub addr: String,
+
+    /// Number of requests processed at once, others get 503
+    #[clap(short, long, value_parser, default_value_t = 4)]
+    pub jobs: usize,
+
+    /// Seconds to wait for the result before answering 504
+    #[clap(long, value_parser, default_value_t = 10)]
+    pub timeout: u64,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct RpcArgs {
+    /// Number of requests processed at once
+    #[clap(short, long, value_parser, default_value_t = 4)]
+    pub jobs: usize,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct MeasureArgs {
+    /// The word rhymes are found for (use ' to mind the stress)
+    #[clap(value_parser)]
+    pub to_find: String,
+
+    /// The word distance is measured to
+    #[clap(value_parser)]
+    pub measured: String,
+
+    /// Mean theme name (one from config/themes.yaml)
+    #[clap(short, long, value_parser)]
+    pub theme: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct TranscribeArgs {
+    /// Words (the dictionary is loaded only for words without stress marks)
+    #[clap(value_parser, required = true)]
+    pub words: Vec<String>,
+
+    /// Phonetic alphabet (by default — the inner notation: * is voiced, ^ is palatalized)
+    #[clap(long, value_enum)]
+    pub notation: Option<PhoneticAlphabet>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct StressArgs {
+    /// Words without stress marks
+    #[clap(value_parser, required = true)]
+    pub words: Vec<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ThemesCommand {
+    /// Print names of all themes
+    List,
+    /// Print words of the theme
+    Show {
+        #[clap(value_parser)]
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DictCommand {
+    /// Print speech part, popularity rank and all forms of the word
+    Lookup {
+        #[clap(value_parser)]
+        word: String,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+pub struct AnalyzeArgs {
+    /// The word (use ' to mind the stress, otherwise it is looked up in the dictionary)
+    #[clap(value_parser)]
+    pub word: String,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct EvaluateArgs {
+    /// YAML file with golden queries and thresholds
+    #[clap(value_parser, default_value = "config/golden.yaml")]
+    pub golden: PathBuf,
+
+    /// Override cut-off for precision and nDCG from the file
+    #[clap(short, value_parser)]
+    pub k: Option<usize>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct TuneArgs {
+    /// YAML file with `good`/`bad` pairs and `ranked` lists (see tuner module)
+    #[clap(value_parser)]
+    pub pairs: PathBuf,
+
+    /// Settings to tune separated with "+" (by default — everything word distance depends on)
+    #[clap(long, value_parser)]
+    pub params: Option<String>,
+
+    /// Maximal number of optimizer iterations
+    #[clap(short, long, value_parser, default_value_t = 300)]
+    pub iterations: usize,
+
+    /// Where to write the profile (e.g. config/profiles/tuned.yaml); stdout if not set
+    #[clap(short, long, value_parser)]
+    pub output: Option<PathBuf>,
+}
+
+/// loads settings with profile and overrides applied
+pub fn settings_from_args(dir: &Path, args: &Args) -> Result<GeneralSettings, String> {
+    load_settings(dir, args.profile.as_deref(), &args.set)
+}
+
+/// `profile` is a name from config/profiles, `overrides` are like `stresses.weight=80`
+pub fn load_settings<S>(
+    dir: &Path,
+    profile: Option<&str>,
+    overrides: &[S],
+) -> Result<GeneralSettings, String>
+where
+    S: Deref<Target = str>,
+{
+    let gs = match profile {
+        Some(name) => GeneralSettings::load_profile(dir, name)?,
+        None => GeneralSettings::try_load_default(dir)?,
+    };
+    gs.with_overrides(overrides)
+}
+
+/// parts of hyphenated compounds that are never stressed (кто-то, кое-что, пойдём-ка)
+const UNSTRESSED_PARTS: [&str; 8] = ["то", "либо", "нибудь", "ка", "таки", "кое", "де", "с"];
+
+/// input is normalized first (see normalizer module), so «карАндаш», «каранда́ш» and «Кaрандаш» with Latin «a» work too;
+/// for patterns (see pattern module) returns the word the found ones are compared with
 pub fn string2word(wc: &WordCollector, to_find: &str) -> Result<Word, String> {
-    if to_find.chars().all(|c| match c {
-        '+' | '!' => true,
-        _ => false,
-    }) {
-        Ok(Word::new(to_find, false))
+    if is_pattern(to_find) {
+        return Ok(Pattern::parse(to_find)?.representative());
+    }
+    let to_find = normalize(to_find);
+    if !to_find.contains('\'') {
+        // ё is often written as е, so «все» may be both «все» and «всё»
+        match wc.get_words_ignoring_yo(&to_find)[..] {
+            [] => compound2word(wc, &to_find).ok_or_else(|| "Word not found; Please mind the stress with «'» (and «`» for secondary stresses)".to_string()),
+            [found] => Ok(found.clone()),
+            ref variants => Err(format!(
+                "«{}» is ambiguous, please choose one of: {}",
+                to_find,
+                variants.iter().map(|w| w.stressed_src()).collect::<Vec<_>>().join(", ")
+            )),
+        }
     } else {
-        let to_find = to_find.to_lowercase();
-        if !to_find.contains('\'') && !to_find.contains('!') {
-            let found = wc.get_word(&to_find);
-            if let Some(founded_some) = found {
-                Ok(founded_some.clone())
-            } else {
-                Err("Word not found; Please mind the stress with «'» (and «`» for secondary stresses)".to_string())
-            }
-        } else {
-            let chrs: Vec<char> = to_find.chars().collect();
-            // check whether the word is correct
-            for i in 0..chrs.len() {
-                let c = chrs[i];
-                match c {
-                    'а'..='я' => {}
-                    'ё' => {}
-                    '`' | '\'' => {
-                        let previous_c = chrs
-                            .get(i - 1)
-                            .ok_or("Stress symbol at start of the word".to_string())?;
-                        if !(ALL_VOWELS.contains(previous_c)) {
-                            return Err("Stress not after the vowel".to_owned());
-                        }
+        let chrs: Vec<char> = to_find.chars().collect();
+        // check whether the word is correct
+        for i in 0..chrs.len() {
+            let c = chrs[i];
+            match c {
+                'а'..='я' => {}
+                'ё' => {}
+                '`' | '\'' => {
+                    let previous_c = i
+                        .checked_sub(1)
+                        .map(|i| chrs[i])
+                        .ok_or("Stress symbol at start of the word".to_string())?;
+                    if !(ALL_VOWELS.contains(&previous_c)) {
+                        return Err("Stress not after the vowel".to_owned());
                     }
-                    '+' | '!' => {}
-                    _ => return Err(format!("Unknown charachter {}", c)),
                 }
+                '-' => {}
+                _ => return Err(format!("Unknown charachter {}", c)),
             }
-            // construct it
-            Ok(Word::new(&to_find, false))
+        }
+        // construct it
+        Ok(Word::new(&to_find, false))
+    }
+}
+
+/// stresses each part of a hyphenated compound by the dictionary: «северо-запад» → «се`веро-за'пад»;
+/// the last found part gets the primary stress, None if no part is found
+fn compound2word(wc: &WordCollector, to_find: &str) -> Option<Word> {
+    if !to_find.contains('-') {
+        return None;
+    }
+    let mut parts: Vec<String> = to_find.split('-').map(|p| p.to_string()).collect();
+    let mut found = vec![];
+    for (i, part) in parts.iter_mut().enumerate() {
+        if UNSTRESSED_PARTS.contains(&part.as_str()) {
+            continue;
+        }
+        if let Some(w) = wc.get_words_ignoring_yo(part).first() {
+            *part = w.stressed_src();
+            found.push(i);
         }
     }
+    let (&last, others) = found.split_last()?;
+    for &i in others {
+        parts[i] = parts[i].replace('\'', "`");
+    }
+    if parts[last].contains('`') && !parts[last].contains('\'') {
+        parts[last] = parts[last].replace('`', "'");
+    }
+    Some(Word::new(&parts.join("-"), false))
+}
+
+/// the same as `string2word` for words with stress marks (the dictionary is not needed for them)
+pub fn string2word_no_dict(s: &str) -> Result<Word, String> {
+    let s = normalize(s);
+    if s.contains('\'') || s.contains('ё') {
+        Ok(Word::new(&s, false))
+    } else {
+        Err("Please mind the stress with «'» (dictionary is not loaded)".to_string())
+    }
 }
 
 pub fn split_by_plus(rps: Option<String>) -> Vec<String> {
@@ -135,43 +472,518 @@ pub fn get_theme_by_key(
     .transpose()
 }
 
-/// debug function to get distances between two words
+/// debug function to get distances between two words (YAML if `format` is None)
 /// don't use it for production purpose (it is rather slow)
 pub fn measure(
     wc: &WordCollector,
     mf: &'_ MeanStrThemes,
     gs: &'_ GeneralSettings,
-    args: &'_ Args,
+    args: &'_ MeasureArgs,
+    format: Option<OutputFormat>,
 ) -> Result<String, String> {
+    measure_with(wc, mf, gs, args, |r| match format {
+        Some(f) => render(&[r], f),
+        None => Ok(serde_yaml::to_string(r).or(Err("Error in yaml creating"))?),
+    })?
+}
+
+/// gives the distance with all subdistances to `f`
+/// (the result refers to the words parsed here, so it can't be returned)
+pub fn measure_with<T>(
+    wc: &WordCollector,
+    mf: &'_ MeanStrThemes,
+    gs: &'_ GeneralSettings,
+    args: &'_ MeasureArgs,
+    f: impl FnOnce(&WordDistanceResult) -> T,
+) -> Result<T, String> {
     let theme = get_theme_by_key(wc, mf, args.theme.clone())?;
-    let word = string2word(wc, &args.to_find.clone())?;
-    let info = FindingInfo::new(wc, &word, gs, theme.as_ref());
+    measure_with_theme(wc, gs, args, theme.as_ref(), f)
+}
 
-    let measured_s = args.measure.as_ref().ok_or("No measure value")?;
+/// the same as `measure_with`, but with the theme already made (`args.theme` is ignored)
+pub fn measure_with_theme<T>(
+    wc: &WordCollector,
+    gs: &'_ GeneralSettings,
+    args: &'_ MeasureArgs,
+    theme: Option<&MeanTheme>,
+    f: impl FnOnce(&WordDistanceResult) -> T,
+) -> Result<T, String> {
+    let word = string2word(wc, &args.to_find)?;
+    let info = FindingInfo::new(wc, &word, gs, theme);
 
-    let measured = string2word(wc, measured_s)?;
+    let measured = string2word(wc, &args.measured)?;
     let mut r = WordDistanceResult::new(&word, &measured, gs);
-    if let Some(i) = wc.get_forms(measured_s) {
+    if let Some(i) = wc.get_forms(&normalize(&args.measured)) {
         r.add_form_dists(&info, *i);
     }
-
-    Ok(serde_yaml::to_string(&r).or(Err("Error in yaml creating"))?)
+    Ok(f(&r))
 }
+
 pub fn find_from_args<'a>(
     wc: &'a WordCollector,
     mf: &'_ MeanStrThemes,
     gs: &'_ GeneralSettings,
-    args: &'_ Args,
+    args: &'_ FindArgs,
 ) -> Result<Vec<WordDistanceResult<'a>>, String> {
     let theme = get_theme_by_key(wc, mf, args.theme.clone())?;
-    let rps = split_by_plus(args.rps.clone());
-    let word = string2word(wc, &args.to_find.clone())?;
-    let info = FindingInfo::new(wc, &word, gs, theme.as_ref());
-    let words = wc.find_best(&info, rps.iter().map(|s| &**s).collect(), args.top_n)?;
+    find_with_theme(wc, gs, args, theme.as_ref())
+}
+
+/// what `args` ask to find: the word (the representative for a pattern), the pattern
+/// and parts of speech to remove
+fn query_from_args(
+    wc: &WordCollector,
+    args: &FindArgs,
+) -> Result<(Word, Option<Pattern>, Vec<String>), String> {
+    let to_find = args.to_find.as_deref().ok_or("Nothing to find")?;
+    let pattern = is_pattern(to_find)
+        .then(|| Pattern::parse(to_find))
+        .transpose()?;
+    let word = match &pattern {
+        Some(pattern) => pattern.representative(),
+        None => string2word(wc, to_find)?,
+    };
+    Ok((word, pattern, split_by_plus(args.rps.clone())))
+}
+
+/// the same as `find_from_args`, but with the theme already made (`args.theme` is ignored)
+pub fn find_with_theme<'a>(
+    wc: &'a WordCollector,
+    gs: &'_ GeneralSettings,
+    args: &'_ FindArgs,
+    theme: Option<&MeanTheme>,
+) -> Result<Vec<WordDistanceResult<'a>>, String> {
+    let (word, pattern, rps) = query_from_args(wc, args)?;
+    let info = FindingInfo::new(wc, &word, gs, theme).with_pattern(pattern.as_ref());
+    let rps = rps.iter().map(|s| &**s).collect();
+    let words = match args.sound_play {
+        Some(kind) => wc.find_sound_play(&info, kind, rps, args.top_n),
+        None => wc.find_best(&info, rps, args.top_n)?,
+    };
     Ok(words)
 }
 
-#[allow(dead_code)]
+/// every candidate for `args` (`args.top_n` is ignored) to get results page by page
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+pub fn search_from_args<'a>(
+    wc: &'a WordCollector,
+    mf: &'_ MeanStrThemes,
+    gs: &'_ GeneralSettings,
+    args: &'_ FindArgs,
+) -> Result<SearchSession<'a>, String> {
+    let theme = get_theme_by_key(wc, mf, args.theme.clone())?;
+    let (word, pattern, rps) = query_from_args(wc, args)?;
+    let info = FindingInfo::new(wc, &word, gs, theme.as_ref()).with_pattern(pattern.as_ref());
+    let rps = rps.iter().map(|s| &**s).collect();
+    match args.sound_play {
+        Some(kind) => Ok(wc.search_sound_play(&info, kind, rps)),
+        None => wc.search(&info, rps),
+    }
+}
+
+pub fn word_play_from_args<'a>(
+    wc: &'a WordCollector,
+    gs: &'_ GeneralSettings,
+    args: &'_ FindArgs,
+) -> Result<Vec<WordPlayResult<'a>>, String> {
+    let to_find = args.to_find.as_deref().ok_or("Nothing to find")?;
+    let top_n = args.top_n as usize;
+    Ok(match args.word_play.ok_or("No word play kind")? {
+        WordPlay::Anagram => wc.find_anagrams(&string2word(wc, to_find)?, top_n),
+        WordPlay::Paronym => wc.find_paronyms(&string2word(wc, to_find)?, gs, top_n),
+        WordPlay::Homophone => {
+            let phrase = to_find
+                .split_whitespace()
+                .map(|s| string2word(wc, s))
+                .collect::<Result<Vec<Word>, String>>()?;
+            wc.find_homophones(&phrase, gs, top_n)
+        }
+    })
+}
+
+/// the word as shown: split into syllables with `args.hyphenate`, ё replaced with `args.no_yo`
+fn shown_src(word: &Word, args: &FindArgs) -> String {
+    let src = if args.hyphenate {
+        hyphenate(&word.src).join("-")
+    } else {
+        word.src.clone()
+    };
+    if args.no_yo {
+        remove_yo(&src)
+    } else {
+        src
+    }
+}
+
+/// human-readable list of found words (or all subdistances if `args.debug`)
+pub fn show_found(words: &[WordDistanceResult], args: &FindArgs) -> String {
+    if args.debug {
+        return format!("{:?}", words);
+    }
+    let shown = words
+        .iter()
+        .map(|r| {
+            let src = shown_src(r.word, args);
+            match args.notation {
+                Some(n) => format!("{} [{}]", src, r.word.render(n)),
+                None => src,
+            }
+        })
+        .collect::<Vec<String>>();
+    format!("{:?}", shown)
+}
+
+/// found words as records for `--format`: ё is removed from `word` with `args.no_yo`,
+/// `syllables` are added with `args.hyphenate` and `transcription` with `args.notation`
+pub fn found_records(words: &[WordDistanceResult], args: &FindArgs) -> Result<Vec<Value>, String> {
+    words
+        .iter()
+        .map(|r| {
+            let mut record = serde_json::to_value(r).map_err(|e| e.to_string())?;
+            if args.no_yo {
+                record["word"] = remove_yo(&r.word.src).into();
+            }
+            if args.hyphenate {
+                record["syllables"] = shown_src(r.word, args).into();
+            }
+            if let Some(n) = args.notation {
+                record["transcription"] = r.word.render(n).into();
+            }
+            Ok(record)
+        })
+        .collect()
+}
+
+pub fn show_word_play(results: &[WordPlayResult], args: &FindArgs) -> String {
+    if args.debug {
+        return format!("{:?}", results);
+    }
+    format!(
+        "{:?}",
+        results
+            .iter()
+            .map(|r| if args.no_yo {
+                remove_yo(&r.src())
+            } else {
+                r.src()
+            })
+            .collect::<Vec<String>>()
+    )
+}
+
+/// word play as records for `--format`: ё is removed from `words` with `args.no_yo`
+pub fn word_play_records(
+    results: &[WordPlayResult],
+    args: &FindArgs,
+) -> Result<Vec<Value>, String> {
+    results
+        .iter()
+        .map(|r| {
+            let mut record = serde_json::to_value(r).map_err(|e| e.to_string())?;
+            if args.no_yo {
+                record["words"] = r.words.iter().map(|w| remove_yo(&w.src)).collect();
+            }
+            Ok(record)
+        })
+        .collect()
+}
+
+/// words for commands that need the dictionary only for words without stress marks
+/// (it takes a while to load)
+pub fn strings2words(dir: &PathBuf, strings: &[String]) -> Result<Vec<Word>, String> {
+    if strings.iter().all(|s| string2word_no_dict(s).is_ok()) {
+        strings.iter().map(|s| string2word_no_dict(s)).collect()
+    } else {
+        let wc = WordCollector::load_default(dir);
+        strings.iter().map(|s| string2word(&wc, s)).collect()
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Transcription {
+    pub word: String,
+    pub transcription: String,
+}
+
+impl Display for Transcription {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} [{}]", self.word, self.transcription)
+    }
+}
+
+/// transcription of every word; inner notation if `notation` is None
+pub fn transcribe(words: &[Word], notation: Option<PhoneticAlphabet>) -> Vec<Transcription> {
+    words
+        .iter()
+        .map(|w| Transcription {
+            word: w.stressed_src(),
+            transcription: match notation {
+                Some(n) => w.render(n),
+                None => w.transcription(),
+            },
+        })
+        .collect()
+}
+
+#[derive(Serialize, Debug)]
+pub struct Stressed {
+    /// as it was given
+    pub word: String,
+    pub stressed: String,
+}
+
+pub fn stress(strings: &[String], words: &[Word]) -> Vec<Stressed> {
+    strings
+        .iter()
+        .zip(words.iter())
+        .map(|(s, w)| Stressed {
+            word: s.clone(),
+            stressed: w.stressed_src(),
+        })
+        .collect()
+}
+
+/// everything known about the word
+#[derive(Serialize, Debug)]
+pub struct Analysis {
+    pub word: String,
+    pub transcription: String,
+    pub ipa: String,
+    pub syllables: Vec<String>,
+    /// number of the stressed syllable from the start (from 1)
+    pub stressed_syllable: Option<usize>,
+    pub clausula: Option<String>,
+    pub post_tonic_tail: Option<String>,
+    pub speech_part: Option<String>,
+}
+
+pub fn analyze(wc: &WordCollector, s: &str) -> Result<Analysis, String> {
+    let word = string2word(wc, s)?;
+    let stressed = word.stressed_src();
+    Ok(Analysis {
+        syllables: hyphenate(&stressed)
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect(),
+        word: stressed,
+        transcription: word.transcription(),
+        ipa: word.render(PhoneticAlphabet::Ipa),
+        stressed_syllable: word.get_stresses().0.map(|i| i + 1),
+        clausula: word.clausula().map(|c| format!("{:?}", c)),
+        post_tonic_tail: word.post_tonic_tail().map(phones2string),
+        speech_part: wc.get_speech_part(&word.src).map(|s| s.to_string()),
+    })
+}
+
+/// the dictionary entry of the word
+#[derive(Serialize, Debug)]
+pub struct DictEntry {
+    pub speech_part: String,
+    /// 0 is the most popular word
+    pub popularity_rank: usize,
+    pub forms: Vec<String>,
+}
+
+pub fn dict_lookup(wc: &WordCollector, s: &str) -> Result<DictEntry, String> {
+    let word = string2word(wc, s)?;
+    let &group = wc
+        .get_forms(&word.src)
+        .ok_or_else(|| format!("«{}» is not in the dictionary", word.src))?;
+    let (forms, words) = wc.get_form_group(group).ok_or("Broken dictionary")?;
+    let mut stressed: Vec<String> = words.iter().map(|w| w.stressed_src()).collect();
+    stressed.dedup();
+    Ok(DictEntry {
+        speech_part: forms.speech_part.clone(),
+        popularity_rank: group,
+        forms: stressed,
+    })
+}
+
+/// a line of the poem with its last word
+#[derive(Serialize, Debug)]
+pub struct PoemLine {
+    pub text: String,
+    /// the last word with the stress
+    pub word: Option<String>,
+    pub clausula: Option<String>,
+    /// letter in the rhyme scheme
+    pub rhyme: Option<char>,
+    /// number of the line (from 1) this one rhymes with
+    pub rhymes_with: Option<usize>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PoemAnalysis {
+    /// like «ABAB CCDD», stanzas are separated with spaces, lines with unknown words are «?»
+    pub scheme: String,
+    pub lines: Vec<PoemLine>,
+}
+
+fn scheme_letter(n: usize) -> char {
+    ('A'..='Z').chain('a'..='z').nth(n).unwrap_or('?')
+}
+
+/// rhyme scheme by the last words of lines: a line rhymes with the closest of `poem.window`
+/// previous lines if the distance is not more than `poem.rhyme_threshold`
+pub fn analyze_poem(wc: &WordCollector, gs: &GeneralSettings, text: &str) -> PoemAnalysis {
+    let mut lines: Vec<PoemLine> = vec![];
+    // number of non-empty line, last word and the index in `lines`
+    let mut previous: Vec<(usize, Word, usize)> = vec![];
+    let (mut non_empty, mut letters) = (0, 0);
+
+    for line in text.lines() {
+        let mut res = PoemLine {
+            text: line.trim().to_string(),
+            word: None,
+            clausula: None,
+            rhyme: None,
+            rhymes_with: None,
+            error: None,
+        };
+        if res.text.is_empty() {
+            lines.push(res);
+            continue;
+        }
+        non_empty += 1;
+        let last = res
+            .text
+            .split_whitespace()
+            .map(|t| t.trim_matches(|c: char| !c.is_alphabetic() && !"'`\u{301}".contains(c)))
+            .rev()
+            .find(|t| t.chars().any(char::is_alphabetic));
+        let word = match last.map(|w| string2word(wc, w)) {
+            Some(Ok(word)) => word,
+            Some(Err(s)) => {
+                res.error = Some(s);
+                lines.push(res);
+                continue;
+            }
+            None => {
+                res.error = Some("No words".to_string());
+                lines.push(res);
+                continue;
+            }
+        };
+
+        let best = previous
+            .iter()
+            .filter(|(n, _, _)| non_empty - n <= gs.poem.window)
+            .map(|(_, w, i)| (WordDistanceResult::new(w, &word, gs).dist.into_inner(), *i))
+            .filter(|(dist, _)| *dist <= gs.poem.rhyme_threshold)
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        match best {
+            Some((_, i)) => {
+                res.rhyme = lines[i].rhyme;
+                res.rhymes_with = Some(i + 1);
+            }
+            None => {
+                res.rhyme = Some(scheme_letter(letters));
+                letters += 1;
+            }
+        }
+        res.word = Some(word.stressed_src());
+        res.clausula = word.clausula().map(|c| format!("{:?}", c));
+        previous.push((non_empty, word, lines.len()));
+        lines.push(res);
+    }
+
+    let scheme = lines
+        .iter()
+        .map(|l| match (&*l.text, l.rhyme) {
+            ("", _) => ' ',
+            (_, Some(letter)) => letter,
+            (_, None) => '?',
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    PoemAnalysis { scheme, lines }
+}
+
+/// sorted names of the themes
+pub fn theme_names(mf: &MeanStrThemes) -> Vec<&str> {
+    let mut names: Vec<&str> = mf.str_themes.keys().map(|k| &**k).collect();
+    names.sort();
+    names
+}
+
+pub fn theme_words<'a>(mf: &'a MeanStrThemes, name: &str) -> Result<&'a [String], String> {
+    mf.str_themes
+        .get(name)
+        .map(|words| &words[..])
+        .ok_or_else(|| format!("Unknown theme: {}", name))
+}
+
+#[derive(Serialize, Debug)]
+pub struct TuneReport {
+    pub comparisons: usize,
+    pub loss_before: f32,
+    pub loss_after: f32,
+    pub accuracy_before: f32,
+    pub accuracy_after: f32,
+}
+
+impl Display for TuneReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Comparisons: {}\nLoss: {:.4} → {:.4}\nCorrectly ordered: {:.1}% → {:.1}%",
+            self.comparisons,
+            self.loss_before,
+            self.loss_after,
+            self.accuracy_before * 100.0,
+            self.accuracy_after * 100.0
+        )
+    }
+}
+
+/// tunes settings on labelled pairs, returns the profile YAML and the report;
+/// the dictionary is loaded only if some words are without stress marks
+pub fn tune(
+    dir: &PathBuf,
+    gs: &GeneralSettings,
+    args: &TuneArgs,
+) -> Result<(String, TuneReport), String> {
+    let raw: RawTuningSet = yaml_read(&args.pairs)?;
+    let all_stressed = raw.words().all(|w| w.contains('\'') || w.contains('ё'));
+    let wc = (!all_stressed).then(|| WordCollector::load_default(dir));
+    let set = TuningSet::new(&raw, |s| match &wc {
+        Some(wc) => string2word(wc, s),
+        None => string2word_no_dict(s),
+    })?;
+
+    let params = match &args.params {
+        Some(p) => split_by_plus(Some(p.clone())),
+        None => DEFAULT_TUNED.iter().map(|p| p.to_string()).collect(),
+    };
+    let result = Tuner::new(&set, gs, &params)?.run(args.iterations);
+
+    let report = TuneReport {
+        comparisons: set.len(),
+        loss_before: result.loss_before,
+        loss_after: result.loss_after,
+        accuracy_before: result.accuracy_before,
+        accuracy_after: result.accuracy_after,
+    };
+    let profile = serde_yaml::to_string(&result.profile).map_err(|err| err.to_string())?;
+    Ok((profile, report))
+}
+
+pub fn evaluate_golden(
+    wc: &WordCollector,
+    mf: &MeanStrThemes,
+    gs: &GeneralSettings,
+    args: &EvaluateArgs,
+) -> Result<EvaluationReport, String> {
+    let mut golden: GoldenSet = yaml_read(&args.golden)?;
+    if let Some(k) = args.k {
+        golden.k = k;
+    }
+    evaluate(wc, mf, gs, &golden)
+}
+
 pub fn find<'a, S>(
     wc: &'a WordCollector,
     gs: &'_ GeneralSettings,
@@ -184,3 +996,43 @@ where S: Deref<Target = str> {
     let info = FindingInfo::new(wc, &to_find, gs, theme);
     wc.find_best(&info, rps.iter().map(|s| s.deref()).collect(), top_n)
 }
+
+#[cfg(test)]
+#[test]
+fn check_string2word() {
+    use crate::reader::VECTOR_DIM;
+    let i2w = ["кто", "север", "запад", "все"].map(|s| s.to_string()).to_vec();
+    let zaliz = [
+        ("кто", "мс+кто'+"),
+        ("север", "с+се'вер+;а"),
+        ("запад", "с+за'пад+"),
+        ("все", "мс+все'+вс+;1ё"),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect();
+    let wc = WordCollector::new(i2w, zaliz, vec![[0.0; VECTOR_DIM]; 4]);
+    let stressed = |s: &str| string2word(&wc, s).map(|w| w.stressed_src());
+
+    assert_eq!(stressed("Кто"), Ok("кто'".to_string()));
+    assert_eq!(stressed("«кто-то»"), Ok("кто'-то".to_string()));
+    assert_eq!(stressed("север-запад"), Ok("се`вер-за'пад".to_string()));
+    assert_eq!(stressed("северa"), Ok("се'вера".to_string()));
+    assert_eq!(stressed("кАрандаш"), Ok("ка'рандаш".to_string()));
+    assert_eq!(stressed("каранда\u{301}ш"), Ok("каранда'ш".to_string()));
+    assert_eq!(
+        stressed("все"),
+        Err("«все» is ambiguous, please choose one of: все', всё".to_string())
+    );
+    assert!(stressed("то-то-то").is_err());
+    assert!(stressed("'кот").is_err());
+
+    // punctuation doesn't make a pattern
+    assert_eq!(
+        string2word(&wc, "ко'т?").map(|w| w.src),
+        Ok("кот".to_string())
+    );
+    assert_eq!(stressed("(кто?)"), Ok("кто'".to_string()));
+    assert_eq!(stressed("кто!"), Ok("кто'".to_string()));
+    assert_eq!(stressed("+!ко"), Ok("+!ко".to_string()));
+}
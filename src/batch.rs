@@ -0,0 +1,382 @@
+/*
+Rust implementation of advanced ryhmes finder
+Copyright (C) 2022  Andrej Sitnikov (sitandr, andr-sitnikov@mail.ru)
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+Module that answers many queries with the dictionary loaded once (`quickpoeter batch`):
+one query per line, «theme=», «rps=» and «top_n=» after it override the defaults from the command line.
+Results are printed as soon as they are ready, but always in the order of lines,
+even if queries are processed in several threads
+*/
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::api::{
+    find_from_args, found_records, show_found, show_word_play, word_play_from_args,
+    word_play_records, BatchArgs, FindArgs,
+};
+use crate::finder::WordCollector;
+use crate::output::{OutputFormat, RecordStream};
+use crate::reader::{GeneralSettings, MeanStrThemes};
+
+/// file or stdin (if `path` is None or «-»)
+pub fn open_input(path: Option<&Path>) -> Result<Box<dyn BufRead + Send>, String> {
+    match path {
+        Some(p) if p != Path::new("-") => File::open(p)
+            .map(|f| Box::new(BufReader::new(f)) as Box<dyn BufRead + Send>)
+            .map_err(|err| format!("Can't read {:?}: {}", p, err)),
+        _ => Ok(Box::new(BufReader::new(std::io::stdin()))),
+    }
+}
+
+/// find arguments for the line; None for empty lines and comments
+pub fn line_args(line: &str, defaults: &BatchArgs) -> Result<Option<FindArgs>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let mut args = FindArgs {
+        theme: defaults.theme.clone(),
+        rps: defaults.rps.clone(),
+        top_n: defaults.top_n,
+        notation: defaults.notation,
+        no_yo: defaults.no_yo,
+        hyphenate: defaults.hyphenate,
+        sound_play: defaults.sound_play,
+        word_play: defaults.word_play,
+        ..FindArgs::new("")
+    };
+    let mut query = vec![];
+    for token in line.split_whitespace() {
+        match token.split_once('=') {
+            Some(("theme", v)) => args.theme = Some(v.to_string()),
+            Some(("rps", v)) => args.rps = Some(v.to_string()),
+            Some(("top_n", v)) => {
+                args.top_n = v.parse().map_err(|_| format!("Bad top_n: {}", v))?
+            }
+            _ => query.push(token),
+        }
+    }
+    if query.is_empty() {
+        return Err("Nothing to find".to_string());
+    }
+    args.to_find = Some(query.join(" "));
+    Ok(Some(args))
+}
+
+enum Answer {
+    Text(String),
+    Records(Vec<Value>),
+}
+
+/// every result with the query it was found for
+#[derive(Serialize)]
+struct BatchRecord<'a, T> {
+    query: &'a str,
+    #[serde(flatten)]
+    result: &'a T,
+}
+
+fn records<T: Serialize>(query: &str, results: &[T]) -> Result<Answer, String> {
+    results
+        .iter()
+        .map(|result| serde_json::to_value(BatchRecord { query, result }))
+        .collect::<Result<Vec<_>, _>>()
+        .map(Answer::Records)
+        .map_err(|e| e.to_string())
+}
+
+fn answer(
+    wc: &WordCollector,
+    mf: &MeanStrThemes,
+    gs: &GeneralSettings,
+    args: &FindArgs,
+    format: Option<OutputFormat>,
+) -> Result<Answer, String> {
+    let query = args.to_find.as_deref().unwrap_or_default();
+    if args.word_play.is_some() {
+        let v = word_play_from_args(wc, gs, args)?;
+        return match format {
+            Some(_) => records(query, &word_play_records(&v, args)?),
+            None => Ok(Answer::Text(show_word_play(&v, args))),
+        };
+    }
+    let v = find_from_args(wc, mf, gs, args)?;
+    match format {
+        Some(_) => records(query, &found_records(&v, args)?),
+        None => Ok(Answer::Text(show_found(&v, args))),
+    }
+}
+
+/// applies `f` to every line in `jobs` threads and gives the results to `out` in the order of lines;
+/// stops reading when `out` returns false
+pub fn process_ordered<T, F, O>(
+    lines: impl Iterator<Item = String> + Send,
+    jobs: usize,
+    f: F,
+    mut out: O,
+) where
+    T: Send,
+    F: Fn(&str) -> T + Sync,
+    O: FnMut(String, T) -> bool,
+{
+    if jobs <= 1 {
+        for line in lines {
+            let res = f(&line);
+            if !out(line, res) {
+                break;
+            }
+        }
+        return;
+    }
+
+    let lines = Mutex::new(lines.enumerate());
+    let stop = AtomicBool::new(false);
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            let tx = tx.clone();
+            let (lines, f, stop) = (&lines, &f, &stop);
+            scope.spawn(move || loop {
+                if stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                let next = lines.lock().unwrap().next();
+                let Some((i, line)) = next else { break };
+                let res = f(&line);
+                if tx.send((i, line, res)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for (i, line, res) in rx.iter() {
+            pending.insert(i, (line, res));
+            while let Some((line, res)) = pending.remove(&next) {
+                if !out(line, res) {
+                    // the workers finish their current lines and don't read more
+                    stop.store(true, Ordering::Relaxed);
+                    return;
+                }
+                next += 1;
+            }
+        }
+    });
+}
+
+/// answers every line of `input`, errors are written to `err` with the line;
+/// returns the number of failed lines (input that can't be read, e.g. not UTF-8, is an error)
+#[allow(clippy::too_many_arguments)]
+pub fn run_batch(
+    wc: &WordCollector,
+    mf: &MeanStrThemes,
+    gs: &GeneralSettings,
+    args: &BatchArgs,
+    format: Option<OutputFormat>,
+    input: impl BufRead + Send,
+    out: &mut impl Write,
+    err: &mut impl Write,
+) -> Result<usize, String> {
+    let mut stream = format.map(RecordStream::new);
+    let mut failed = 0;
+    let mut write_error = None;
+
+    let read_error = Mutex::new(None);
+    let lines = input.lines().map_while(|line| {
+        line.map_err(|e| *read_error.lock().unwrap() = Some(e.to_string()))
+            .ok()
+    });
+    let f = |line: &str| {
+        line_args(line, args)?
+            .map(|a| answer(wc, mf, gs, &a, format))
+            .transpose()
+    };
+    process_ordered(lines, args.jobs, f, |line, res| {
+        let text = match (res, stream.as_mut()) {
+            (Ok(None), _) => return true,
+            (Ok(Some(Answer::Text(text))), _) => format!("{}: {}", line.trim(), text),
+            (Ok(Some(Answer::Records(records))), Some(stream)) => match stream.push(&records) {
+                Ok(text) => text,
+                Err(s) => {
+                    write_error = Some(s);
+                    return false;
+                }
+            },
+            (Ok(Some(Answer::Records(_))), None) => {
+                unreachable!("records are only made for formats")
+            }
+            (Err(s), _) => {
+                failed += 1;
+                if let Err(e) = writeln!(err, "{}: {}", line.trim(), s) {
+                    write_error = Some(e.to_string());
+                    return false;
+                }
+                return true;
+            }
+        };
+        if text.is_empty() {
+            return true;
+        }
+        match writeln!(out, "{}", text).and_then(|_| out.flush()) {
+            Ok(_) => true,
+            Err(e) => {
+                write_error = Some(e.to_string());
+                false
+            }
+        }
+    });
+
+    if let Some(e) = write_error {
+        return Err(e);
+    }
+    if let Some(e) = read_error.into_inner().unwrap() {
+        return Err(format!("Can't read queries: {}", e));
+    }
+    if let Some(stream) = stream {
+        let text = stream.finish()?;
+        if !text.is_empty() {
+            writeln!(out, "{}", text).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(failed)
+}
+
+#[cfg(test)]
+#[test]
+fn check_batch() {
+    use crate::api::{Args, Command};
+    use crate::reader::VECTOR_DIM;
+    use clap::Parser;
+    use std::path::PathBuf;
+
+    let gs = GeneralSettings::try_load_default(&PathBuf::from(".")).unwrap();
+    let mf = MeanStrThemes::default();
+    let words = [
+        ("кот", "с+ко'т+;а"),
+        ("рот", "с+ро'т+"),
+        ("крот", "с+кро'т+"),
+        ("дом", "с+до'м+"),
+        ("идти", "г+идти'+"),
+        ("ёж", "с+ё'ж+"),
+    ];
+    let i2w = words.map(|(k, _)| k.to_string()).to_vec();
+    let zaliz = words
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let wc = WordCollector::new(i2w, zaliz, vec![[0.0; VECTOR_DIM]; words.len()]);
+
+    let batch_args = |cli: &[&str]| match Args::parse_from(cli).command {
+        Some(Command::Batch(b)) => b,
+        _ => unreachable!(),
+    };
+    let defaults = batch_args(&["quickpoeter", "batch", "-n", "2"]);
+    let a = line_args("  ко'т top_n=1 rps=г ", &defaults)
+        .unwrap()
+        .unwrap();
+    assert_eq!(a.to_find.as_deref(), Some("ко'т"));
+    assert_eq!((a.top_n, a.rps.as_deref()), (1, Some("г")));
+    assert!(line_args("# comment", &defaults).unwrap().is_none());
+    assert!(line_args("top_n=x ко'т", &defaults).is_err());
+
+    let run = |args: &BatchArgs, format, input: &str| {
+        let (mut out, mut err) = (vec![], vec![]);
+        let failed = run_batch(
+            &wc,
+            &mf,
+            &gs,
+            args,
+            format,
+            input.as_bytes(),
+            &mut out,
+            &mut err,
+        );
+        (
+            failed.unwrap(),
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(err).unwrap(),
+        )
+    };
+    let input = "ко'т top_n=1\n\nпр'о\nдо'м top_n=1\n";
+    let (failed, out, err) = run(&defaults, None, input);
+    assert_eq!(failed, 1);
+    assert_eq!(out, "ко'т top_n=1: [\"кот\"]\nдо'м top_n=1: [\"дом\"]\n");
+    assert!(err.starts_with("пр'о: "));
+
+    // the same order in parallel
+    let parallel = batch_args(&["quickpoeter", "batch", "-n", "2", "-j", "4"]);
+    let many: String = (0..20).map(|i| ["ко'т\n", "до'м\n"][i % 2]).collect();
+    assert_eq!(run(&parallel, None, &many), run(&defaults, None, &many));
+
+    let (_, out, _) = run(&defaults, Some(OutputFormat::Csv), "ко'т\nдо'м\n");
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert!(lines[0].starts_with("query,dist,"));
+    assert!(lines[1].starts_with("ко'т,") && lines[3].starts_with("до'м,"));
+
+    let mut out = vec![];
+    let not_utf8 = b"ko'\xff\n".as_slice();
+    let res = run_batch(
+        &wc,
+        &mf,
+        &gs,
+        &defaults,
+        None,
+        not_utf8,
+        &mut out,
+        &mut vec![],
+    );
+    assert!(res.is_err());
+
+    // nothing more is read after the output is closed
+    let read = std::sync::atomic::AtomicUsize::new(0);
+    let lines = (0..1000).map(|i| {
+        read.fetch_add(1, Ordering::Relaxed);
+        i.to_string()
+    });
+    let slow = |line: &str| thread::sleep(std::time::Duration::from_millis(line.len() as u64));
+    process_ordered(lines, 4, slow, |_, _| false);
+    assert!(read.into_inner() < 100);
+
+    // shown as in the text output
+    let shown = batch_args(&[
+        "quickpoeter",
+        "batch",
+        "-n",
+        "1",
+        "--no-yo",
+        "--hyphenate",
+        "--notation",
+        "ipa",
+    ]);
+    let (_, out, _) = run(&shown, Some(OutputFormat::Jsonl), "ё'ж\n");
+    let record: Value = serde_json::from_str(&out).unwrap();
+    assert_eq!(record["word"], "еж");
+    assert_eq!(record["syllables"], "еж");
+    assert_eq!(record["transcription"], "ˈjoʂ");
+}
@@ -0,0 +1,305 @@
+/*
+Rust implementation of advanced ryhmes finder
+Copyright (C) 2022  Andrej Sitnikov (sitandr, andr-sitnikov@mail.ru)
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+Module that checks settings for ranges and numerical safety
+(so that bad config gives readable errors instead of NaN panics deep inside the finder)
+*/
+
+use std::fmt::{Display, Formatter};
+
+use crate::reader::{
+    AlliterationSettings, ConsonantDistanceSettings, ConsonantStructureSettings, GeneralSettings,
+    MeaningSettings, MiscSettings, PoemSettings, PopularitySettings, SamePartSpeechSettings,
+    SoundPlaySettings, StressSettings, UnsymmetricalSettings, VowelDistanceSettings,
+    WordPlaySettings,
+};
+
+/// single problem in settings; `path` is YAML path like `stresses.distance.denominator`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingsDiagnostic {
+    pub path: String,
+    pub message: String,
+}
+
+impl Display for SettingsDiagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+struct Checker {
+    prefix: &'static str,
+    diagnostics: Vec<SettingsDiagnostic>,
+}
+
+impl Checker {
+    fn report(&mut self, field: &str, message: String) {
+        self.diagnostics.push(SettingsDiagnostic {
+            path: format!("{}.{}", self.prefix, field),
+            message,
+        });
+    }
+
+    /// returns false if already reported
+    fn finite(&mut self, field: &str, value: f32) -> bool {
+        if value.is_finite() {
+            true
+        } else {
+            self.report(field, format!("must be a finite number, got {}", value));
+            false
+        }
+    }
+
+    fn positive(&mut self, field: &str, value: f32) {
+        if self.finite(field, value) && value <= 0.0 {
+            self.report(field, format!("must be positive, got {}", value));
+        }
+    }
+
+    fn non_negative(&mut self, field: &str, value: f32) {
+        if self.finite(field, value) && value < 0.0 {
+            self.report(field, format!("must be non-negative, got {}", value));
+        }
+    }
+
+    /// `(x + shift)^pow` where x ≥ 0 is used as a divisor
+    /// zero base gives division by zero, negative base with fractional pow gives NaN
+    fn divisor_base(&mut self, shift_field: &str, shift: f32, pow_field: &str, pow: f32) {
+        if self.finite(shift_field, shift)
+            && self.finite(pow_field, pow)
+            && pow != 0.0
+            && shift <= 0.0
+        {
+            self.report(
+                shift_field,
+                format!(
+                    "must be positive while {} ≠ 0 (otherwise the base may become zero or negative), got {}",
+                    pow_field, shift
+                ),
+            );
+        }
+    }
+
+    /// `map` is named `features` for consonants
+    fn distance_map(&mut self, name: &str, map: &[(f32, f32)], pow: f32, denominator: f32) {
+        for (i, (x, y)) in map.iter().enumerate() {
+            self.finite(&format!("{}.{}.0", name, i), *x);
+            self.finite(&format!("{}.{}.1", name, i), *y);
+        }
+        // zero pow makes all distances equal, negative gives infinity for close letters
+        self.positive("pow", pow);
+        self.positive("denominator", denominator);
+    }
+}
+
+impl GeneralSettings {
+    /// checks ranges and numerical safety of every parameter; empty vec if everything is ok
+    pub fn validate(&self) -> Vec<SettingsDiagnostic> {
+        let mut diagnostics = vec![];
+        diagnostics.append(&mut self.misc.validate());
+        diagnostics.append(&mut self.stresses.validate());
+        diagnostics.append(&mut self.consonant_structure.validate());
+        diagnostics.append(&mut self.alliteration.validate());
+        diagnostics.append(&mut self.meaning.validate());
+        diagnostics.append(&mut self.popularity.validate());
+        diagnostics.append(&mut self.unsymmetrical.validate());
+        diagnostics.append(&mut self.same_speech_part.validate());
+        diagnostics.append(&mut self.sound_play.validate());
+        diagnostics.append(&mut self.word_play.validate());
+        diagnostics.append(&mut self.poem.validate());
+        diagnostics
+    }
+
+    /// returns self if valid, otherwise all diagnostics joined into one message
+    pub fn validated(self) -> Result<Self, String> {
+        let diagnostics = self.validate();
+        if diagnostics.is_empty() {
+            Ok(self)
+        } else {
+            Err(format!(
+                "Invalid settings:\n{}",
+                diagnostics
+                    .iter()
+                    .map(|d| format!("  {}", d))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ))
+        }
+    }
+}
+
+macro_rules! validator {
+    ($sett: ty, $prefix: expr, |$s: ident, $c: ident| $body: block) => {
+        impl $sett {
+            pub fn validate(&self) -> Vec<SettingsDiagnostic> {
+                let $s = self;
+                let mut $c = Checker {
+                    prefix: $prefix,
+                    diagnostics: vec![],
+                };
+                $body
+                $c.diagnostics
+            }
+        }
+    };
+}
+
+validator!(MiscSettings, "misc", |s, c| {
+    c.finite("same_cons_end", s.same_cons_end);
+    c.finite("length_diff_fine", s.length_diff_fine);
+});
+
+validator!(StressSettings, "stresses", |s, c| {
+    c.non_negative("k_not_strict_stress", s.k_not_strict_stress);
+    c.non_negative("k_strict_stress", s.k_strict_stress);
+    c.non_negative("bad_rythm", s.bad_rythm);
+    c.non_negative("weight", s.weight);
+    c.divisor_base(
+        "shift_syll_ending",
+        s.shift_syll_ending,
+        "pow_syll_ending",
+        s.pow_syll_ending,
+    );
+    c.divisor_base("asympt_shift", s.asympt_shift, "asympt", s.asympt);
+    c.diagnostics.append(&mut s.distance.validate());
+});
+
+validator!(VowelDistanceSettings, "stresses.distance", |s, c| {
+    c.distance_map("map", &s.map, s.pow, s.denominator);
+});
+
+validator!(ConsonantStructureSettings, "consonant_structure", |s, c| {
+    c.positive("pow", s.pow);
+    c.non_negative("weight", s.weight);
+    c.divisor_base(
+        "shift_syll_ending",
+        s.shift_syll_ending,
+        "pow_syll_ending",
+        s.pow_syll_ending,
+    );
+    c.divisor_base("asympt_shift", s.asympt_shift, "asympt", s.asympt);
+});
+
+validator!(AlliterationSettings, "alliteration", |s, c| {
+    c.divisor_base(
+        "shift_coord",
+        s.shift_coord,
+        "pow_coord_delta",
+        s.pow_coord_delta,
+    );
+    c.divisor_base(
+        "shift_syll_ending",
+        s.shift_syll_ending,
+        "pow_syll_ending",
+        s.pow_syll_ending,
+    );
+    c.non_negative("permutations", s.permutations);
+    c.non_negative("weight", s.weight);
+    c.divisor_base("asympt_shift", s.asympt_shift, "asympt", s.asympt);
+    c.diagnostics.append(&mut s.distance.validate());
+});
+
+validator!(
+    ConsonantDistanceSettings,
+    "alliteration.distance",
+    |s, c| {
+        c.distance_map("features", &s.features, s.pow, s.denominator);
+        c.non_negative("weights.place", s.weights.place);
+        c.non_negative("weights.manner", s.weights.manner);
+        c.non_negative("weights.voicing", s.weights.voicing);
+        c.non_negative("weights.palatalization", s.weights.palatalization);
+    }
+);
+
+validator!(MeaningSettings, "meaning", |s, c| {
+    c.positive("pow", s.pow);
+    c.positive("single_pow", s.single_pow);
+    c.non_negative("single_weight", s.single_weight);
+    c.non_negative("weight", s.weight);
+});
+
+validator!(PopularitySettings, "popularity", |s, c| {
+    // the most popular word has index 0, so 0^pow must be finite
+    c.non_negative("pow", s.pow);
+    c.non_negative("weight", s.weight);
+});
+
+validator!(UnsymmetricalSettings, "unsymmetrical", |s, c| {
+    c.non_negative("optimal_length", s.optimal_length);
+    c.non_negative("less_w", s.less_w);
+    c.positive("less_pow", s.less_pow);
+    c.non_negative("more_w", s.more_w);
+    c.positive("more_pow", s.more_pow);
+});
+
+validator!(SamePartSpeechSettings, "same_speech_part", |s, c| {
+    c.finite("verb", s.verb);
+    c.finite("noun", s.noun);
+    c.finite("adj", s.adj);
+    c.finite("adv", s.adv);
+});
+
+validator!(SoundPlaySettings, "sound_play", |s, c| {
+    c.divisor_base("shift", s.shift, "pow", s.pow);
+    c.positive("depth", s.depth as f32);
+    c.non_negative("stressed_vowel", s.stressed_vowel);
+    c.non_negative("weight", s.weight);
+});
+
+validator!(WordPlaySettings, "word_play", |s, c| {
+    c.non_negative("max_dist", s.max_dist);
+});
+
+validator!(PoemSettings, "poem", |s, c| {
+    c.non_negative("rhyme_threshold", s.rhyme_threshold);
+    c.positive("window", s.window as f32);
+});
+
+#[cfg(test)]
+#[test]
+fn check_settings() {
+    use std::path::PathBuf;
+    let gs = GeneralSettings::try_load_default(&PathBuf::from(".")).unwrap();
+    assert_eq!(gs.validate(), vec![]);
+
+    let mut bad = gs.clone();
+    bad.stresses.distance.denominator = -1.0;
+    bad.alliteration.asympt_shift = 0.0;
+    bad.alliteration.distance.features[3].1 = f32::NAN;
+    bad.alliteration.distance.weights.voicing = -0.5;
+    bad.consonant_structure.pow = 0.0;
+    bad.sound_play.depth = 0;
+    let paths: Vec<String> = bad.validate().into_iter().map(|d| d.path).collect();
+    assert_eq!(
+        paths,
+        vec![
+            "stresses.distance.denominator",
+            "consonant_structure.pow",
+            "alliteration.asympt_shift",
+            "alliteration.distance.features.3.1",
+            "alliteration.distance.weights.voicing",
+            "sound_play.depth",
+        ]
+    );
+
+    // zero shift is ok if the power is zero
+    let mut ok = gs;
+    ok.stresses.asympt = 0.0;
+    ok.stresses.asympt_shift = 0.0;
+    assert!(ok.validated().is_ok());
+}
@@ -0,0 +1,261 @@
+/*
+Rust implementation of advanced ryhmes finder
+Copyright (C) 2022  Andrej Sitnikov (sitandr, andr-sitnikov@mail.ru)
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+Module that evaluates search quality on golden rhymes (precision@k, MRR, nDCG),
+so settings changes can be checked for regressions
+*/
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+use crate::api::{find, get_theme_by_key, string2word};
+use crate::finder::WordCollector;
+use crate::reader::{GeneralSettings, MeanStrThemes};
+
+fn default_k() -> usize {
+    10
+}
+
+fn default_depth() -> u32 {
+    100
+}
+
+/// single query with rhymes that are expected to be found (without stress marks, as in results)
+#[derive(Deserialize)]
+pub struct GoldenQuery {
+    pub query: String,
+    #[serde(default)]
+    pub theme: Option<String>,
+    pub expected: Vec<String>,
+}
+
+/// see config/golden.yaml
+#[derive(Deserialize)]
+pub struct GoldenSet {
+    /// cut-off for precision and nDCG
+    #[serde(default = "default_k")]
+    pub k: usize,
+    /// how many results are searched (MRR counts only them)
+    #[serde(default = "default_depth")]
+    pub depth: u32,
+    /// minimal mean metrics for evaluation to pass
+    #[serde(default)]
+    pub thresholds: Metrics,
+    pub queries: Vec<GoldenQuery>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Metrics {
+    pub precision: f32,
+    pub mrr: f32,
+    pub ndcg: f32,
+}
+
+/// share of first `k` found words that are expected
+pub fn precision_at_k(found: &[&str], expected: &HashSet<&str>, k: usize) -> f32 {
+    let hits = found
+        .iter()
+        .take(k)
+        .filter(|w| expected.contains(*w))
+        .count();
+    hits as f32 / k as f32
+}
+
+/// 1/rank of the first expected word, 0 if none found
+pub fn reciprocal_rank(found: &[&str], expected: &HashSet<&str>) -> f32 {
+    found
+        .iter()
+        .position(|w| expected.contains(w))
+        .map_or(0.0, |i| 1.0 / (i + 1) as f32)
+}
+
+/// normalized discounted cumulative gain with binary relevance
+pub fn ndcg_at_k(found: &[&str], expected: &HashSet<&str>, k: usize) -> f32 {
+    let discount = |i: usize| 1.0 / (i as f32 + 2.0).log2();
+    let dcg: f32 = found
+        .iter()
+        .take(k)
+        .enumerate()
+        .filter(|(_, w)| expected.contains(*w))
+        .map(|(i, _)| discount(i))
+        .sum();
+    let ideal: f32 = (0..expected.len().min(k)).map(discount).sum();
+    if ideal == 0.0 {
+        0.0
+    } else {
+        dcg / ideal
+    }
+}
+
+impl Metrics {
+    pub fn measure(found: &[&str], expected: &HashSet<&str>, k: usize) -> Self {
+        Metrics {
+            precision: precision_at_k(found, expected, k),
+            mrr: reciprocal_rank(found, expected),
+            ndcg: ndcg_at_k(found, expected, k),
+        }
+    }
+
+    pub fn mean(all: &[Metrics]) -> Self {
+        let n = all.len().max(1) as f32;
+        Metrics {
+            precision: all.iter().map(|m| m.precision).sum::<f32>() / n,
+            mrr: all.iter().map(|m| m.mrr).sum::<f32>() / n,
+            ndcg: all.iter().map(|m| m.ndcg).sum::<f32>() / n,
+        }
+    }
+
+    /// whether every metric is not less than in `thresholds`
+    pub fn passes(&self, thresholds: &Metrics) -> bool {
+        self.precision >= thresholds.precision
+            && self.mrr >= thresholds.mrr
+            && self.ndcg >= thresholds.ndcg
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct QueryReport {
+    pub query: String,
+    pub metrics: Metrics,
+    /// expected words that weren't found at all
+    pub missing: Vec<String>,
+}
+
+pub struct EvaluationReport {
+    pub k: usize,
+    pub queries: Vec<QueryReport>,
+    pub mean: Metrics,
+    pub thresholds: Metrics,
+}
+
+impl EvaluationReport {
+    pub fn passed(&self) -> bool {
+        self.mean.passes(&self.thresholds)
+    }
+
+    /// every query and then «mean» and «threshold» as queries, for output in tables
+    pub fn rows(&self) -> Vec<QueryReport> {
+        let summary = |query: &str, metrics| QueryReport {
+            query: query.to_string(),
+            metrics,
+            missing: vec![],
+        };
+        let mut rows = self.queries.clone();
+        rows.push(summary("mean", self.mean));
+        rows.push(summary("threshold", self.thresholds));
+        rows
+    }
+}
+
+impl Display for EvaluationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let header = format!("P@{}", self.k);
+        writeln!(
+            f,
+            "{:<16} {:>6} {:>6} {:>6}  missing",
+            "query", header, "MRR", "nDCG"
+        )?;
+        for q in self.queries.iter() {
+            writeln!(
+                f,
+                "{:<16} {:>6.3} {:>6.3} {:>6.3}  {}",
+                q.query,
+                q.metrics.precision,
+                q.metrics.mrr,
+                q.metrics.ndcg,
+                q.missing.join(", ")
+            )?;
+        }
+        writeln!(
+            f,
+            "{:<16} {:>6.3} {:>6.3} {:>6.3}",
+            "mean", self.mean.precision, self.mean.mrr, self.mean.ndcg
+        )?;
+        write!(
+            f,
+            "{:<16} {:>6.3} {:>6.3} {:>6.3}  {}",
+            "threshold",
+            self.thresholds.precision,
+            self.thresholds.mrr,
+            self.thresholds.ndcg,
+            if self.passed() { "PASSED" } else { "FAILED" }
+        )
+    }
+}
+
+/// runs every golden query through `find_best` and measures the results
+pub fn evaluate(
+    wc: &WordCollector,
+    mf: &MeanStrThemes,
+    gs: &GeneralSettings,
+    golden: &GoldenSet,
+) -> Result<EvaluationReport, String> {
+    let mut queries = vec![];
+    for q in golden.queries.iter() {
+        let theme = get_theme_by_key(wc, mf, q.theme.clone())?;
+        let word = string2word(wc, &q.query).map_err(|err| format!("«{}»: {}", q.query, err))?;
+        let results = find::<&str>(wc, gs, word, theme.as_ref(), &[], golden.depth)?;
+
+        let found: Vec<&str> = results.iter().map(|r| &*r.word.src).collect();
+        let expected: HashSet<&str> = q.expected.iter().map(|s| &**s).collect();
+        queries.push(QueryReport {
+            query: q.query.clone(),
+            metrics: Metrics::measure(&found, &expected, golden.k),
+            missing: q
+                .expected
+                .iter()
+                .filter(|e| !found.contains(&&***e))
+                .cloned()
+                .collect(),
+        });
+    }
+
+    let all: Vec<Metrics> = queries.iter().map(|q| q.metrics).collect();
+    Ok(EvaluationReport {
+        k: golden.k,
+        mean: Metrics::mean(&all),
+        thresholds: golden.thresholds,
+        queries,
+    })
+}
+
+#[cfg(test)]
+#[test]
+fn check_metrics() {
+    let expected: HashSet<&str> = ["кровь", "вновь"].into_iter().collect();
+    let found = ["бровь", "кровь", "морковь", "вновь"];
+
+    assert_eq!(precision_at_k(&found, &expected, 2), 0.5);
+    assert_eq!(precision_at_k(&found, &expected, 4), 0.5);
+    assert_eq!(reciprocal_rank(&found, &expected), 0.5);
+    assert_eq!(reciprocal_rank(&found[1..], &expected), 1.0);
+    assert_eq!(reciprocal_rank(&found[..1], &expected), 0.0);
+
+    // (1/log₂3 + 1/log₂5) / (1 + 1/log₂3)
+    let ndcg = ndcg_at_k(&found, &expected, 4);
+    assert!((ndcg - 0.651).abs() < 1e-3);
+    assert_eq!(ndcg_at_k(&["кровь", "вновь"], &expected, 4), 1.0);
+
+    let m = Metrics::measure(&found, &expected, 4);
+    assert!(m.passes(&Metrics::default()));
+    assert!(!m.passes(&Metrics {
+        precision: 0.6,
+        ..m
+    }));
+}
@@ -0,0 +1,327 @@
+/*
+Rust implementation of advanced ryhmes finder
+Copyright (C) 2022  Andrej Sitnikov (sitandr, andr-sitnikov@mail.ru)
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+Module with C ABI for using the finder from other languages (feature `ffi`),
+the header is ffi/quickpoeter.h, the example and test is ffi/test.c.
+Ownership:
+  qp_collector_new → qp_collector_free, qp_find → qp_results_free;
+  strings passed in are only read during the call and stay owned by the caller;
+  strings and results returned are owned by the library: words live until their results are freed,
+  qp_last_error() lives until the next call in the same thread.
+Functions never unwind into C: panics become errors
+*/
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+
+use crate::api::{find_from_args, load_settings, FindArgs};
+use crate::finder::WordCollector;
+use crate::reader::{GeneralSettings, MeanStrThemes};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(s: String) {
+    let s = CString::new(s.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(s));
+}
+
+/// runs `f`, on error or panic saves the message and returns `default`
+fn guarded<T>(default: T, f: impl FnOnce() -> Result<T, String>) -> T {
+    LAST_ERROR.with(|e| *e.borrow_mut() = None);
+    let res = catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|panic| {
+        Err(match panic.downcast::<String>() {
+            Ok(s) => *s,
+            Err(panic) => panic
+                .downcast::<&str>()
+                .map_or("Unknown panic".to_string(), |s| s.to_string()),
+        })
+    });
+    res.unwrap_or_else(|s| {
+        set_error(s);
+        default
+    })
+}
+
+/// NULL is None
+unsafe fn optional_str<'a>(s: *const c_char) -> Result<Option<&'a str>, String> {
+    if s.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map(Some)
+        .map_err(|_| "String is not UTF-8".to_string())
+}
+
+/// dictionary, themes and settings loaded once
+pub struct QpCollector {
+    wc: WordCollector,
+    mf: MeanStrThemes,
+    gs: GeneralSettings,
+}
+
+#[repr(C)]
+pub struct QpResult {
+    pub word: *const c_char,
+    pub dist: f32,
+    pub misc: f32,
+    pub vowel: f32,
+    pub cons: f32,
+    pub structure: f32,
+    pub meaning: f32,
+    pub popular: f32,
+    pub unsymm: f32,
+    pub same_part: f32,
+}
+
+pub struct QpResults {
+    results: Vec<QpResult>,
+    // words `results` point to
+    _words: Vec<CString>,
+}
+
+/// Loads the dictionary from `dir/res` and settings from `dir/config`
+/// (with `profile` from config/profiles if not NULL); NULL on error.
+///
+/// # Safety
+/// `dir` and `profile` are NULL or valid C strings
+#[no_mangle]
+pub unsafe extern "C" fn qp_collector_new(
+    dir: *const c_char,
+    profile: *const c_char,
+) -> *mut QpCollector {
+    guarded(ptr::null_mut(), || {
+        let dir = PathBuf::from(optional_str(dir)?.unwrap_or("."));
+        let gs = load_settings(&dir, optional_str(profile)?, &[] as &[&str])?;
+        let collector = QpCollector {
+            wc: WordCollector::load_default(&dir),
+            mf: MeanStrThemes::load_default(&dir),
+            gs,
+        };
+        Ok(Box::into_raw(Box::new(collector)))
+    })
+}
+
+/// # Safety
+/// `collector` is NULL or from `qp_collector_new` and is not used after
+#[no_mangle]
+pub unsafe extern "C" fn qp_collector_free(collector: *mut QpCollector) {
+    if !collector.is_null() {
+        drop(Box::from_raw(collector));
+    }
+}
+
+/// Finds `top_n` rhymes for `query` (use ' to mind the stress); `theme` and `rps`
+/// (parts of speech to remove separated with "+") may be NULL;
+/// `settings` are `n_settings` overrides like "stresses.weight=80" for this search only.
+/// NULL on error.
+///
+/// # Safety
+/// `collector` is from `qp_collector_new`, strings are NULL or valid C strings,
+/// `settings` points to `n_settings` valid C strings (or is NULL if `n_settings` is 0)
+#[no_mangle]
+pub unsafe extern "C" fn qp_find(
+    collector: *const QpCollector,
+    query: *const c_char,
+    theme: *const c_char,
+    rps: *const c_char,
+    top_n: u32,
+    settings: *const *const c_char,
+    n_settings: usize,
+) -> *mut QpResults {
+    guarded(ptr::null_mut(), || {
+        let c = collector.as_ref().ok_or("No collector")?;
+        let query = optional_str(query)?.ok_or("Nothing to find")?;
+        let overrides = match n_settings {
+            0 => vec![],
+            n => std::slice::from_raw_parts(settings, n)
+                .iter()
+                .map(|&s| optional_str(s)?.ok_or_else(|| "Setting is NULL".to_string()))
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+        let gs = c.gs.clone().with_overrides(&overrides)?;
+        let args = FindArgs {
+            theme: optional_str(theme)?.map(str::to_string),
+            rps: optional_str(rps)?.map(str::to_string),
+            top_n,
+            ..FindArgs::new(query)
+        };
+        let found = find_from_args(&c.wc, &c.mf, &gs, &args)?;
+
+        let words = found
+            .iter()
+            .map(|r| CString::new(r.word.src.clone()).map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let results = found
+            .iter()
+            .zip(words.iter())
+            .map(|(r, w)| {
+                let [misc, vowel, cons, structure, meaning, popular, unsymm, same_part] =
+                    r.components();
+                QpResult {
+                    word: w.as_ptr(),
+                    dist: r.dist.into_inner(),
+                    misc,
+                    vowel,
+                    cons,
+                    structure,
+                    meaning,
+                    popular,
+                    unsymm,
+                    same_part,
+                }
+            })
+            .collect();
+        let results = QpResults {
+            results,
+            _words: words,
+        };
+        Ok(Box::into_raw(Box::new(results)))
+    })
+}
+
+/// # Safety
+/// `results` is NULL or from `qp_find` and is not freed
+#[no_mangle]
+pub unsafe extern "C" fn qp_results_len(results: *const QpResults) -> usize {
+    results.as_ref().map_or(0, |r| r.results.len())
+}
+
+/// The `index`-th result (the best is the first), NULL if out of range;
+/// lives until `qp_results_free`.
+///
+/// # Safety
+/// `results` is NULL or from `qp_find` and is not freed
+#[no_mangle]
+pub unsafe extern "C" fn qp_results_get(
+    results: *const QpResults,
+    index: usize,
+) -> *const QpResult {
+    results
+        .as_ref()
+        .and_then(|r| r.results.get(index))
+        .map_or(ptr::null(), |r| r as *const QpResult)
+}
+
+/// # Safety
+/// `results` is NULL or from `qp_find` and is not used after
+#[no_mangle]
+pub unsafe extern "C" fn qp_results_free(results: *mut QpResults) {
+    if !results.is_null() {
+        drop(Box::from_raw(results));
+    }
+}
+
+/// Message of the last error in this thread or NULL if the last call succeeded
+#[no_mangle]
+pub extern "C" fn qp_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// 1 if the library was built with this version of the header
+#[no_mangle]
+pub extern "C" fn qp_abi_version() -> c_int {
+    1
+}
+
+#[cfg(test)]
+#[test]
+fn check_ffi() {
+    use crate::reader::VECTOR_DIM;
+
+    let words = [
+        ("кот", "с+ко'т+;а"),
+        ("рот", "с+ро'т+"),
+        ("крот", "с+кро'т+"),
+        ("дом", "с+до'м+"),
+    ];
+    let i2w = words.map(|(k, _)| k.to_string()).to_vec();
+    let zaliz = words
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let collector = Box::into_raw(Box::new(QpCollector {
+        wc: WordCollector::new(i2w, zaliz, vec![[0.0; VECTOR_DIM]; words.len()]),
+        mf: MeanStrThemes::default(),
+        gs: GeneralSettings::try_load_default(&PathBuf::from(".")).unwrap(),
+    }));
+    let error = || unsafe {
+        CStr::from_ptr(qp_last_error())
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+
+    unsafe {
+        let query = CString::new("ко'т").unwrap();
+        let results = qp_find(
+            collector,
+            query.as_ptr(),
+            ptr::null(),
+            ptr::null(),
+            2,
+            ptr::null(),
+            0,
+        );
+        assert!(qp_last_error().is_null());
+        assert_eq!(qp_results_len(results), 2);
+        let best = &*qp_results_get(results, 0);
+        assert_eq!(CStr::from_ptr(best.word).to_str(), Ok("кот"));
+        let sum = best.misc + best.vowel + best.cons + best.structure;
+        assert!(best.dist >= sum);
+        assert!(qp_results_get(results, 2).is_null());
+        qp_results_free(results);
+
+        let bad = CString::new("x.y=1").unwrap();
+        let settings = [bad.as_ptr()];
+        let results = qp_find(
+            collector,
+            query.as_ptr(),
+            ptr::null(),
+            ptr::null(),
+            2,
+            settings.as_ptr(),
+            1,
+        );
+        assert!(results.is_null());
+        assert!(!error().is_empty());
+        let results = qp_find(
+            collector,
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+            2,
+            ptr::null(),
+            0,
+        );
+        assert!(results.is_null());
+        assert_eq!(error(), "Nothing to find");
+        assert_eq!(qp_results_len(results), 0);
+        qp_collector_free(collector);
+
+        // missing dictionary is an error, not a panic
+        let missing = CString::new("/nonexistent").unwrap();
+        assert!(qp_collector_new(missing.as_ptr(), ptr::null()).is_null());
+        assert!(!error().is_empty());
+    }
+}
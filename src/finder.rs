@@ -24,6 +24,7 @@ use std::fmt::Display;
 use std::path::PathBuf;
 
 use crate::meaner::MeanTheme;
+use crate::pattern::Pattern;
 use crate::reader::GeneralSettings;
 use crate::reader::MeaningSettings;
 use crate::reader::PopularitySettings;
@@ -32,13 +33,14 @@ use crate::reader::UnsymmetricalSettings;
 use crate::reader::VECTOR_DIM;
 use crate::reader::vec16_to_vec32;
 use crate::reader::vec2arr;
-use crate::translator_ru::symbol_id;
+use crate::sound_play::{sound_play_dist, SoundPlay};
+use crate::translator_ru::{remove_yo, symbol_id};
 use crate::translator_struct::Word;
 use ordered_float::NotNan;
 use serde::ser::SerializeStruct;
 use serde::Serialize;
 use serde_pickle::DeOptions;
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
 use std::collections::BinaryHeap;
 use std::collections::HashMap;
 use std::fmt::Debug;
@@ -55,6 +57,8 @@ pub struct FindingInfo<'collector, 'finding> {
     pub part_of_speech: Option<&'finding str>,
     pub gs: &'finding GeneralSettings,
     pub theme: Option<&'finding MeanTheme>,
+    /// words are filtered by it; if None and the word has «+» or «!», it is parsed from the word
+    pub pattern: Option<&'finding Pattern>,
     // all these refs need to live only through finding time
     // except collector; collector should live through all the time distance result exists
 }
@@ -72,8 +76,14 @@ impl<'collector: 'finding, 'finding> FindingInfo<'collector, 'finding> {
             part_of_speech: wc.get_speech_part(&to_find.src),
             gs,
             theme,
+            pattern: None,
         }
     }
+
+    pub fn with_pattern(mut self, pattern: Option<&'finding Pattern>) -> Self {
+        self.pattern = pattern;
+        self
+    }
 }
 
 #[derive(Clone)]
@@ -116,6 +126,30 @@ impl<'collector> WordDistanceResult<'collector> {
         }
     }
 
+    /// distance for sound play inside lines (see sound_play module) instead of rhyme;
+    /// stored as vowel or consonant distance, form distances are not added too
+    pub fn from_sound_play(
+        to_find: &Word,
+        measured: &'collector Word,
+        kind: SoundPlay,
+        gs: &GeneralSettings,
+    ) -> Self {
+        let d = sound_play_dist(to_find, measured, kind, gs);
+        let (vowel, cons) = if kind.by_vowels() { (d, 0.0) } else { (0.0, d) };
+        WordDistanceResult {
+            dist: NotNan::new(d).unwrap(),
+            word: measured,
+            misc: 0.0,
+            vowel,
+            cons,
+            structure: 0.0,
+            meaning: 0.0,
+            popularity: 0.0,
+            unsymmetrical: 0.0,
+            same_part: 0.0,
+        }
+    }
+
     pub fn from_forms(forms_index: usize, info: &FindingInfo<'collector, '_>) -> Self {
         let forms = &info.wc.word_form_groups[forms_index];
         let mut res = forms
@@ -221,6 +255,22 @@ impl<'collector> WordDistanceResult<'collector> {
             }
         }
     }
+
+    /// parts of `dist` in the order of serialization: misc, vowel, cons, struct, meaning,
+    /// popular, unsymm (doesn't affect `dist`) and sameSP
+    #[allow(dead_code)]
+    pub fn components(&self) -> [f32; 8] {
+        [
+            self.misc,
+            self.vowel,
+            self.cons,
+            self.structure,
+            self.meaning,
+            self.popularity,
+            self.unsymmetrical,
+            self.same_part,
+        ]
+    }
 }
 
 impl Ord for WordDistanceResult<'_> {
@@ -271,7 +321,7 @@ impl Serialize for WordDistanceResult<'_> {
     where
         S: serde::Serializer,
     {
-        let mut s = serializer.serialize_struct("WordDistanceResult", 8)?;
+        let mut s = serializer.serialize_struct("WordDistanceResult", 10)?;
         s.serialize_field("dist", &self.dist.into_inner())?;
         s.serialize_field("misc", &self.misc)?;
         s.serialize_field("vowel", &self.vowel)?;
@@ -279,7 +329,6 @@ impl Serialize for WordDistanceResult<'_> {
         s.serialize_field("struct", &self.structure)?;
         s.serialize_field("meaning", &self.meaning)?;
         s.serialize_field("popular", &self.popularity)?;
-        s.serialize_field("popular", &self.popularity)?;
         s.serialize_field("unsymm", &self.unsymmetrical)?;
         s.serialize_field("sameSP", &self.same_part)?;
         s.serialize_field("word", &self.word.src)?;
@@ -339,6 +388,8 @@ impl Display for UnsafeStrSaver {
 }
 
 unsafe impl Sync for UnsafeStrSaver {}
+// the strings are on the heap and are owned by the same WordCollector, so they move together
+unsafe impl Send for UnsafeStrSaver {}
 
 pub struct WordForms {
     pub start_index: usize,
@@ -359,6 +410,9 @@ pub struct WordCollector {
     string2index: HashMap<UnsafeStrSaver, usize>, // word string -> word index
     index2group_index: HashMap<usize, usize>,     // index of word -> index of wordgroup
     stress_indexing: HashMap<(u8, usize), HashSet<usize>>, // (letter, letter_index) -> [matching word index]
+    // word string with е instead of ё -> indexes of words with ё
+    yo_less: HashMap<String, Vec<usize>>,
+    sorted_strings: Vec<usize>, // word indexes from string2index sorted by the string (for prefix search)
 }
 
 impl WordCollector {
@@ -411,11 +465,12 @@ impl WordCollector {
 
                 index2group_index.insert(words.len(), group_index);
 
-                let stress_info = w.get_primary_stress();
-                stress_indexing
-                    .entry(stress_info)
-                    .or_insert(HashSet::new())
-                    .insert(words.len());
+                if let Some(stress_info) = w.get_primary_stress() {
+                    stress_indexing
+                        .entry(stress_info)
+                        .or_insert(HashSet::new())
+                        .insert(words.len());
+                }
 
                 words.push(w);
             }
@@ -426,18 +481,30 @@ impl WordCollector {
             string2index: HashMap::new(),
             index2group_index,
             stress_indexing,
+            yo_less: HashMap::new(),
+            sorted_strings: vec![],
         };
         for wgroup in wc.word_form_groups.iter() {
             for word_index in wgroup.range() {
                 let word_form = &wc.words[word_index];
                 string2index.insert(UnsafeStrSaver::new(&word_form.src), word_index);
+                if word_form.src.contains('ё') {
+                    let same = wc.yo_less.entry(remove_yo(&word_form.src)).or_default();
+                    // the same form may repeat in the group (e.g. nominative and accusative)
+                    if same.iter().all(|&i| wc.words[i].src != word_form.src) {
+                        same.push(word_index);
+                    }
+                }
             }
         }
+        wc.sorted_strings = string2index.values().copied().collect();
+        wc.sorted_strings
+            .sort_unstable_by(|&a, &b| wc.words[a].src.cmp(&wc.words[b].src));
         wc.string2index = string2index;
         wc
     }
 
-    /// the actual finding work: filters words (bad stresses if indexing is enabled), then creates WordDistance objects for all filtered,
+    /// the actual finding work: filters words (bad stresses if indexing is enabled, not matching the pattern), then creates WordDistance objects for all filtered,
     /// pushes them into heap, and returns best *n* results
     /// *ignore* — will skip listed parts of speech  
     pub fn find_best<'c>(
@@ -447,37 +514,135 @@ impl WordCollector {
         top_n: u32,
     ) -> Result<Vec<WordDistanceResult>, String> {
         let mut heap = TopNHeap::new(top_n as usize);
-        let allowed = self.words_with_same_stresses(info.to_find);
-
-        let regexp = info.to_find.get_regexp()?;
-        let mut regexping = false;
-        let allowed = match regexp {
-            Some(reg) => {
-                regexping = true;
-                allowed
-                    .filter(|i| reg.is_match(&self.words[*i].src))
+        self.score_candidates(info, &ignore, |res| heap.push(res))?;
+        Ok(heap.heap.into_sorted_vec())
+    }
+
+    /// the same as `find_best`, but keeps every candidate to get results page by page
+    /// (see `SearchSession`)
+    #[allow(dead_code)]
+    pub fn search<'c>(
+        &'c self,
+        info: &FindingInfo<'c, '_>,
+        ignore: Vec<&str>,
+    ) -> Result<SearchSession<'c>, String> {
+        let mut candidates = vec![];
+        self.score_candidates(info, &ignore, |res| candidates.push(res))?;
+        Ok(SearchSession::new(candidates))
+    }
+
+    /// gives a result for every form group that passes the filters to `f`
+    fn score_candidates<'c>(
+        &'c self,
+        info: &FindingInfo<'c, '_>,
+        ignore: &[&str],
+        mut f: impl FnMut(WordDistanceResult<'c>),
+    ) -> Result<(), String> {
+        let parsed;
+        let pattern = match info.pattern {
+            Some(p) => Some(p),
+            None if info.to_find.has_wildcards() => {
+                parsed = Pattern::parse(&info.to_find.src)?;
+                Some(&parsed)
+            }
+            None => None,
+        };
+
+        let allowed = match pattern {
+            Some(pattern) => {
+                // stresses are used for indexing only if the position of stress is fixed by the pattern
+                let candidates: Box<dyn Iterator<Item = usize> + '_> = match pattern.stress_from_end() {
+                    Some(position) => Box::new(
+                        self.stress_indexing
+                            .iter()
+                            .filter(move |(key, _)| key.1 == position)
+                            .flat_map(|(_, v)| v)
+                            .copied(),
+                    ),
+                    None => Box::new(0..self.words.len()),
+                };
+                candidates
+                    .filter(|i| pattern.matches(&self.words[*i]))
                     .collect::<HashSet<usize>>()
             }
-            None => allowed.collect::<HashSet<usize>>(),
+            None => self
+                .words_with_same_stresses(info.to_find)
+                .collect::<HashSet<usize>>(),
         };
 
         for (wform_index, wform) in self.word_form_groups.iter().enumerate() {
-            if ignore.contains(&&*wform.speech_part) {
+            if ignore.contains(&&*wform.speech_part)
+                || pattern.is_some_and(|p| !p.allows_speech_part(&wform.speech_part))
+            {
                 continue;
             }
-            if info.gs.stresses.indexation || regexping {
+            if info.gs.stresses.indexation || pattern.is_some() {
                 let res = WordDistanceResult::from_froms_with_filter(wform_index, info, &allowed);
 
                 if let Some(res) = res {
-                    heap.push(res);
+                    f(res);
                 }
             } else {
                 let res = WordDistanceResult::from_forms(wform_index, info);
-                heap.push(res);
+                f(res);
             }
         }
 
-        Ok(heap.heap.into_sorted_vec())
+        Ok(())
+    }
+
+    /// finds words for alliteration, assonance or consonance with the given one;
+    /// meaning, popularity and other form distances are added as for rhymes
+    pub fn find_sound_play<'c>(
+        &'c self,
+        info: &FindingInfo<'c, '_>,
+        kind: SoundPlay,
+        ignore: Vec<&str>,
+        top_n: u32,
+    ) -> Vec<WordDistanceResult<'c>> {
+        let mut heap = TopNHeap::new(top_n as usize);
+        self.score_sound_play(info, kind, &ignore, |res| heap.push(res));
+        heap.heap.into_sorted_vec()
+    }
+
+    /// the same as `find_sound_play`, but keeps every candidate (see `SearchSession`)
+    #[allow(dead_code)]
+    pub fn search_sound_play<'c>(
+        &'c self,
+        info: &FindingInfo<'c, '_>,
+        kind: SoundPlay,
+        ignore: Vec<&str>,
+    ) -> SearchSession<'c> {
+        let mut candidates = vec![];
+        self.score_sound_play(info, kind, &ignore, |res| candidates.push(res));
+        SearchSession::new(candidates)
+    }
+
+    fn score_sound_play<'c>(
+        &'c self,
+        info: &FindingInfo<'c, '_>,
+        kind: SoundPlay,
+        ignore: &[&str],
+        mut f: impl FnMut(WordDistanceResult<'c>),
+    ) {
+        for (wform_index, wform) in self.word_form_groups.iter().enumerate() {
+            if ignore.contains(&&*wform.speech_part)
+                || info.pattern.is_some_and(|p| !p.allows_speech_part(&wform.speech_part))
+            {
+                continue;
+            }
+            let res = wform
+                .range()
+                .filter(|&i| info.pattern.is_none_or(|p| p.matches(&self.words[i])))
+                .map(|i| {
+                    WordDistanceResult::from_sound_play(info.to_find, &self.words[i], kind, info.gs)
+                })
+                .min();
+            if let Some(mut res) = res {
+                res.add_form_dists(info, wform_index);
+                f(res);
+            }
+        }
     }
 
     /// returns iterator of corresponding word indexes
@@ -504,8 +669,50 @@ impl WordCollector {
         crate::reader::load_default_word_collector(dir)
     }
 
+    /// if there is no such word, tries to find it as written without ё (if it is unambiguous)
     pub fn get_index(&self, not_stressed: &str) -> Option<&usize> {
-        self.string2index.get(&UnsafeStrSaver::new(not_stressed))
+        self.string2index
+            .get(&UnsafeStrSaver::new(not_stressed))
+            .or_else(|| match self.yo_less.get(not_stressed) {
+                Some(v) if v.len() == 1 => v.first(),
+                _ => None,
+            })
+    }
+
+    /// all words that may be written this way if ё is replaced with е:
+    /// «все» → [все, всё], «еж» → [ёж]; exact match goes first
+    pub fn get_words_ignoring_yo(&self, not_stressed: &str) -> Vec<&Word> {
+        let exact = self.string2index.get(&UnsafeStrSaver::new(not_stressed));
+        let with_yo = self.yo_less.get(not_stressed).into_iter().flatten();
+        exact
+            .into_iter()
+            .chain(with_yo)
+            .map(|&i| &self.words[i])
+            .collect()
+    }
+
+    /// up to `limit` words starting with `prefix`, the most popular first (for completion)
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn words_with_prefix(&self, prefix: &str, limit: usize) -> Vec<&str> {
+        let start = self
+            .sorted_strings
+            .partition_point(|&i| self.words[i].src.as_str() < prefix);
+        let mut found: Vec<usize> = self.sorted_strings[start..]
+            .iter()
+            .copied()
+            .take_while(|&i| self.words[i].src.starts_with(prefix))
+            .collect();
+        found.sort_unstable();
+        found
+            .into_iter()
+            .take(limit)
+            .map(|i| self.words[i].src.as_str())
+            .collect()
+    }
+
+    /// all word forms; forms of one word go together, words are sorted by popularity
+    pub fn words(&self) -> &[Word] {
+        &self.words
     }
 
     /// returns matching group from index of word inside
@@ -513,6 +720,14 @@ impl WordCollector {
         self.index2group_index.get(index)
     }
 
+    /// word forms group by its index (the index is also the popularity rank) and its forms
+    pub fn get_form_group(&self, group_index: usize) -> Option<(&WordForms, &[Word])> {
+        self.word_form_groups
+            .get(group_index)
+            .map(|forms| (forms, &self.words[forms.range()]))
+    }
+
+    #[allow(dead_code)]
     pub fn get_word(&self, not_stressed: &str) -> Option<&Word> {
         self.get_index(not_stressed).map(|&ind| &self.words[ind])
     }
@@ -578,6 +793,44 @@ impl<'collector> TopNHeap<'collector> {
     }
 }
 
+/// every scored candidate of a search, ranked lazily: a page costs only sorting
+/// the results up to its end, distances are never computed again
+pub struct SearchSession<'collector> {
+    ranked: Vec<WordDistanceResult<'collector>>,
+    rest: BinaryHeap<Reverse<WordDistanceResult<'collector>>>,
+}
+
+#[allow(dead_code)]
+impl<'collector> SearchSession<'collector> {
+    pub fn new(candidates: Vec<WordDistanceResult<'collector>>) -> Self {
+        SearchSession {
+            ranked: vec![],
+            rest: candidates.into_iter().map(Reverse).collect(),
+        }
+    }
+
+    /// number of all candidates
+    pub fn len(&self) -> usize {
+        self.ranked.len() + self.rest.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// results from `offset` (0 is the best) to `offset + limit`, less at the end
+    pub fn page(&mut self, offset: usize, limit: usize) -> &[WordDistanceResult<'collector>] {
+        let end = offset.saturating_add(limit).min(self.len());
+        while self.ranked.len() < end {
+            let Some(Reverse(res)) = self.rest.pop() else {
+                break;
+            };
+            self.ranked.push(res);
+        }
+        &self.ranked[offset.min(end)..end]
+    }
+}
+
 /// to stay at stable I use tests as benchmarks. Use them with `cargo test word_collect --release -- --nocapture`
 #[cfg(test)]
 #[test]
@@ -587,7 +840,7 @@ fn word_collect() {
     let current = Instant::now();
     let wc = WordCollector::load_default(&PathBuf::from("."));
     let mf = MeanStrThemes::load_default(&PathBuf::from("."));
-    let gs = GeneralSettings::load_default(&PathBuf::from("."));
+    let gs = GeneralSettings::try_load_default(&PathBuf::from(".")).unwrap();
     println!("Loaded words in {:#?}", current.elapsed());
 
     let current = Instant::now();
@@ -631,15 +884,17 @@ fn word_collect() {
     println!("Found word in {:#?}", current.elapsed());
 
     let current = Instant::now();
+    let pattern = Pattern::parse("!кий pos=п").unwrap();
     println!(
         "{:?}",
         wc.find_best(
-            &FindingInfo::new(&wc, &Word::new("!кий", false), &gs, Some(&theme)),
+            &FindingInfo::new(&wc, &pattern.representative(), &gs, Some(&theme))
+                .with_pattern(Some(&pattern)),
             vec![],
             50
         )
     );
-    println!("Found word «!кий» in {:#?}", current.elapsed());
+    println!("Found word «!кий pos=п» in {:#?}", current.elapsed());
 
     //use std::{thread, time::Duration};
     //let mut wc = wc;
@@ -668,3 +923,79 @@ fn profile_load() {
     println!("Removed stringify");
     thread::sleep(Duration::from_millis(10_000));
 }
+
+#[cfg(test)]
+#[test]
+fn yo_less_lookup() {
+    let i2w = ["ёж", "весь", "всё"].map(|s| s.to_string()).to_vec();
+    let zaliz = [
+        ("ёж", "с+ёж+;а;у;у"),
+        ("весь", "мс-п+ве'сь+вс+;1е'"),
+        ("всё", "мс+всё+"),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect();
+    let wc = WordCollector::new(i2w, zaliz, vec![[0.0; VECTOR_DIM]; 3]);
+
+    assert_eq!(wc.get_word("ёжа").unwrap().src, "ёжа");
+    assert_eq!(wc.get_word("ежа").unwrap().src, "ёжа");
+    assert_eq!(wc.get_word("ежу").unwrap().src, "ёжу");
+    assert_eq!(wc.get_word("все").unwrap().src, "все");
+    assert!(wc.get_word("ежи").is_none());
+
+    let srcs = |s| {
+        wc.get_words_ignoring_yo(s)
+            .iter()
+            .map(|w| w.src.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(srcs("все"), vec!["все", "всё"]);
+    assert_eq!(srcs("всё"), vec!["всё"]);
+    assert_eq!(srcs("ежу"), vec!["ёжу"]);
+
+    assert_eq!(wc.words_with_prefix("вс", 10), vec!["все", "всё"]);
+    assert_eq!(wc.words_with_prefix("ё", 2), vec!["ёж", "ёжа"]);
+    assert_eq!(wc.words_with_prefix("в", 1), vec!["весь"]);
+    assert!(wc.words_with_prefix("я", 10).is_empty());
+}
+
+#[cfg(test)]
+#[test]
+fn search_pages() {
+    let words = [
+        ("кот", "с+ко'т+;а"),
+        ("рот", "с+ро'т+"),
+        ("крот", "с+кро'т+"),
+        ("дом", "с+до'м+"),
+        ("ток", "с+то'к+"),
+        ("пот", "с+по'т+"),
+    ];
+    let i2w = words.map(|(k, _)| k.to_string()).to_vec();
+    let zaliz = words
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let wc = WordCollector::new(i2w, zaliz, vec![[0.0; VECTOR_DIM]; words.len()]);
+    let gs = GeneralSettings::try_load_default(&PathBuf::from(".")).unwrap();
+    let word = Word::new("ко'т", false);
+    let info = FindingInfo::new(&wc, &word, &gs, None);
+
+    let best = wc.find_best(&info, vec![], 100).unwrap();
+    let mut session = wc.search(&info, vec![]).unwrap();
+    assert_eq!(session.len(), best.len());
+
+    let dists = |rs: &[WordDistanceResult]| rs.iter().map(|r| r.dist).collect::<Vec<_>>();
+    let mut paged = dists(session.page(0, 4));
+    paged.extend(dists(session.page(4, 4)));
+    assert_eq!(paged, dists(&best));
+    // earlier pages are kept
+    assert_eq!(dists(session.page(2, 2)), dists(&best[2..4]));
+    assert!(session.page(best.len(), 10).is_empty());
+    assert!(session.page(usize::MAX, usize::MAX).is_empty());
+
+    let kind = SoundPlay::Alliteration;
+    let sound = wc.find_sound_play(&info, kind, vec![], 3);
+    let mut session = wc.search_sound_play(&info, kind, vec![]);
+    assert_eq!(dists(session.page(0, 3)), dists(&sound));
+}
@@ -20,8 +20,31 @@ Module that just imports other modules for using them as library
 */
 
 pub mod api;
+pub mod batch;
+pub mod checker;
+pub mod evaluator;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod finder;
 pub mod meaner;
+pub mod methods;
+pub mod normalizer;
+pub mod output;
+pub mod pattern;
+#[cfg(feature = "python")]
+pub mod python;
 pub mod reader;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod repl;
+pub mod rpc;
+// no sockets and threads in the browser
+#[cfg(not(target_arch = "wasm32"))]
+pub mod serve;
+pub mod sound_play;
+pub mod syllabifier;
 pub mod translator_ru;
 pub mod translator_struct;
+pub mod tuner;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod wordplay;
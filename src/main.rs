@@ -20,50 +20,249 @@ Module that works with cli (isn't included in library)
 */
 
 use clap::Parser;
+use serde::Serialize;
+use serde_json::json;
 use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
 
 mod api;
+mod batch;
+mod checker;
+mod evaluator;
 mod finder;
 mod meaner;
+mod methods;
+mod normalizer;
+mod output;
+mod pattern;
 mod reader;
+#[cfg(not(target_arch = "wasm32"))]
+mod repl;
+mod rpc;
+#[cfg(not(target_arch = "wasm32"))]
+mod serve;
+mod sound_play;
+mod syllabifier;
 mod translator_ru;
 mod translator_struct;
+mod tuner;
+mod wordplay;
 
 #[cfg(test)]
 mod tests;
 
-use crate::api::measure;
-use crate::api::{find_from_args, Args};
+use crate::api::{
+    analyze, dict_lookup, evaluate_golden, find_from_args, found_records, measure,
+    settings_from_args, show_found, show_word_play, stress, strings2words, theme_names,
+    theme_words, transcribe, tune, word_play_from_args, word_play_records, Args, Command,
+    DictCommand, FindArgs, ThemesCommand,
+};
+use crate::batch::{open_input, run_batch};
 use crate::finder::WordCollector;
-use crate::reader::GeneralSettings;
-use crate::reader::MeanStrThemes;
+use crate::methods::State;
+use crate::output::{render, OutputFormat};
+use crate::reader::{GeneralSettings, MeanStrThemes};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::repl::run_repl;
+use crate::rpc::run_rpc;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::serve::HttpServer;
 
 fn main() {
-    let wc = WordCollector::load_default(&PathBuf::new());
-    let mf = MeanStrThemes::load_default(&PathBuf::new());
-    let gs = GeneralSettings::load_default(&PathBuf::new());
     let a = Args::parse();
+    let dir = &a.dir;
+    let gs = match settings_from_args(dir, &a) {
+        Ok(gs) => gs,
+        Err(s) => {
+            eprintln!("{}", s);
+            return;
+        }
+    };
 
-    if a.measure.is_some() {
-        let r = measure(&wc, &mf, &gs, &a);
-        println!(
-            "{}",
-            match r {
-                Ok(r) => r,
-                Err(r) => r,
+    let format = a.format;
+    let res = match &a.command {
+        None => run_find(dir, &gs, &a.find, format),
+        Some(Command::Find(f)) => run_find(dir, &gs, f, format),
+        Some(Command::Batch(b)) => {
+            let wc = WordCollector::load_default(dir);
+            let mf = MeanStrThemes::load_default(dir);
+            open_input(b.input.as_deref())
+                .and_then(|input| {
+                    let (mut out, mut err) = (std::io::stdout(), std::io::stderr());
+                    run_batch(&wc, &mf, &gs, b, format, input, &mut out, &mut err)
+                })
+                .and_then(|failed| match failed {
+                    0 => Ok(String::new()),
+                    n => Err(format!("{} queries failed", n)),
+                })
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        Some(Command::Repl(r)) => {
+            let wc = WordCollector::load_default(dir);
+            let mf = MeanStrThemes::load_default(dir);
+            run_repl(&wc, &mf, gs.clone(), format, r.page, r.history.as_deref())
+                .map(|_| String::new())
+        }
+        // there is no terminal line editor for wasm
+        #[cfg(target_arch = "wasm32")]
+        Some(Command::Repl(_)) => Err("repl is not available in this build".to_string()),
+        #[cfg(not(target_arch = "wasm32"))]
+        Some(Command::Serve(sa)) => {
+            let state = Arc::new(State {
+                wc: WordCollector::load_default(dir),
+                mf: MeanStrThemes::load_default(dir),
+                gs: gs.clone(),
+            });
+            let timeout = Duration::from_secs(sa.timeout);
+            HttpServer::bind(&sa.addr, state, sa.jobs, timeout).map(|server| {
+                eprintln!("Listening on http://{}", sa.addr);
+                server.run();
+                String::new()
+            })
+        }
+        #[cfg(target_arch = "wasm32")]
+        Some(Command::Serve(_)) => Err("serve is not available in this build".to_string()),
+        Some(Command::Rpc(r)) => {
+            let state = State {
+                wc: WordCollector::load_default(dir),
+                mf: MeanStrThemes::load_default(dir),
+                gs: gs.clone(),
+            };
+            let (input, output) = (std::io::stdin().lock(), std::io::stdout());
+            run_rpc(&state, input, output, r.jobs).map(|_| String::new())
+        }
+        Some(Command::Measure(m)) => {
+            let wc = WordCollector::load_default(dir);
+            let mf = MeanStrThemes::load_default(dir);
+            measure(&wc, &mf, &gs, m, format)
+        }
+        Some(Command::Transcribe(t)) => strings2words(dir, &t.words).and_then(|words| {
+            let res = transcribe(&words, t.notation);
+            match format {
+                Some(f) => render(&res, f),
+                None => Ok(lines(&res)),
             }
-        );
-        return;
+        }),
+        Some(Command::Stress(st)) => strings2words(dir, &st.words).and_then(|words| {
+            let res = stress(&st.words, &words);
+            match format {
+                Some(f) => render(&res, f),
+                None => Ok(lines(res.iter().map(|r| &r.stressed))),
+            }
+        }),
+        Some(Command::Themes(t)) => {
+            let mf = MeanStrThemes::load_default(dir);
+            let (key, values) = match t {
+                ThemesCommand::List => ("theme", Ok(theme_names(&mf))),
+                ThemesCommand::Show { name } => (
+                    "word",
+                    theme_words(&mf, name).map(|w| w.iter().map(|s| &**s).collect()),
+                ),
+            };
+            values.and_then(|values| match format {
+                Some(f) => render(
+                    &values.iter().map(|v| json!({ key: v })).collect::<Vec<_>>(),
+                    f,
+                ),
+                None => Ok(values.join("\n")),
+            })
+        }
+        Some(Command::Dict(DictCommand::Lookup { word })) => {
+            let wc = WordCollector::load_default(dir);
+            dict_lookup(&wc, word).and_then(|e| yaml_or(&e, format))
+        }
+        Some(Command::Analyze(an)) => {
+            let wc = WordCollector::load_default(dir);
+            analyze(&wc, &an.word).and_then(|r| yaml_or(&r, format))
+        }
+        Some(Command::Tune(t)) => tune(dir, &gs, t).and_then(|(profile, report)| {
+            let shown = match format {
+                Some(f) => {
+                    if t.output.is_none() {
+                        return Err("Tune with --format needs --output for the profile".to_string());
+                    }
+                    render(&[&report], f)?
+                }
+                None => {
+                    eprintln!("{}", report);
+                    String::new()
+                }
+            };
+            match &t.output {
+                Some(path) => std::fs::write(path, profile)
+                    .map(|_| shown)
+                    .map_err(|err| format!("Can't write {:?}: {}", path, err)),
+                None => Ok(profile),
+            }
+        }),
+        Some(Command::Evaluate(e)) => {
+            let wc = WordCollector::load_default(dir);
+            let mf = MeanStrThemes::load_default(dir);
+            evaluate_golden(&wc, &mf, &gs, e).and_then(|report| {
+                let shown = match format {
+                    Some(f) => render(&report.rows(), f)?,
+                    None => report.to_string(),
+                };
+                if !report.passed() {
+                    println!("{}", shown);
+                    std::process::exit(1);
+                }
+                Ok(shown)
+            })
+        }
+    };
+
+    match res {
+        Ok(out) if out.is_empty() => {}
+        Ok(out) => println!("{}", out),
+        Err(s) => {
+            eprintln!("{}", s);
+            std::process::exit(1);
+        }
     }
+}
 
-    let words = find_from_args(&wc, &mf, &gs, &a);
+/// one item per line
+fn lines<T: ToString>(items: impl IntoIterator<Item = T>) -> String {
+    items
+        .into_iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
-    if a.debug {
-        println!("{:?}", words);
-    } else {
-        match words.map(|v| v.iter().map(|r| &*r.word.src).collect::<Vec<&str>>()) {
-            Ok(v) => println!("{:?}", v),
-            Err(s) => eprintln!("{}", s),
-        }
+/// single record in the chosen format or YAML mapping
+fn yaml_or<T: Serialize>(record: &T, format: Option<OutputFormat>) -> Result<String, String> {
+    match format {
+        Some(f) => render(&[record], f),
+        None => serde_yaml::to_string(record).map_err(|e| e.to_string()),
+    }
+}
+
+/// rhymes, sound play or word play
+fn run_find(
+    dir: &PathBuf,
+    gs: &GeneralSettings,
+    a: &FindArgs,
+    format: Option<OutputFormat>,
+) -> Result<String, String> {
+    let wc = WordCollector::load_default(dir);
+
+    if a.word_play.is_some() {
+        let v = word_play_from_args(&wc, gs, a)?;
+        return match format {
+            Some(f) => render(&word_play_records(&v, a)?, f),
+            None => Ok(show_word_play(&v, a)),
+        };
+    }
+
+    let mf = MeanStrThemes::load_default(dir);
+    let words = find_from_args(&wc, &mf, gs, a)?;
+    match format {
+        Some(f) => render(&found_records(&words, a)?, f),
+        None => Ok(show_found(&words, a)),
     }
 }
@@ -0,0 +1,197 @@
+/*
+Rust implementation of advanced ryhmes finder
+Copyright (C) 2022  Andrej Sitnikov (sitandr, andr-sitnikov@mail.ru)
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+Module with the methods shared by the HTTP server (see the serve module), JSON-RPC (see the rpc module)
+and the JS API (see the wasm module): `State` with everything loaded once and `call` of a method by name
+with JSON parameters. There are no threads and no network here, so it is built for wasm too
+*/
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::api::{
+    analyze, analyze_poem, find_from_args, measure_with, stress, string2word, theme_names,
+    theme_words, transcribe, word_play_from_args, FindArgs, MeasureArgs,
+};
+use crate::finder::WordCollector;
+use crate::reader::{GeneralSettings, MeanStrThemes};
+use crate::sound_play::SoundPlay;
+use crate::translator_ru::PhoneticAlphabet;
+use crate::wordplay::WordPlay;
+
+/// everything requests need; may be shared between threads
+pub struct State {
+    pub wc: WordCollector,
+    pub mf: MeanStrThemes,
+    pub gs: GeneralSettings,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FindRequest {
+    query: String,
+    theme: Option<String>,
+    rps: Option<String>,
+    top_n: Option<u32>,
+    sound_play: Option<SoundPlay>,
+    word_play: Option<WordPlay>,
+    #[serde(default)]
+    settings: Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MeasureRequest {
+    to_find: String,
+    measured: String,
+    theme: Option<String>,
+    #[serde(default)]
+    settings: Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TranscribeRequest {
+    words: Vec<String>,
+    notation: Option<PhoneticAlphabet>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WordsRequest {
+    words: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WordRequest {
+    word: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PoemRequest {
+    text: String,
+    #[serde(default)]
+    settings: Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeRequest {
+    name: String,
+}
+
+fn parse<'de, T: Deserialize<'de>>(params: &'de Value) -> Result<T, String> {
+    T::deserialize(params).map_err(|err| format!("Bad parameters: {}", err))
+}
+
+/// server settings with request overrides applied
+fn settings(state: &State, overrides: &Map<String, Value>) -> Result<GeneralSettings, String> {
+    let overrides: Vec<String> = overrides
+        .iter()
+        .map(|(path, value)| format!("{}={}", path, value))
+        .collect();
+    state.gs.clone().with_overrides(&overrides)
+}
+
+pub const METHODS: [&str; 8] = [
+    "find",
+    "measure",
+    "transcribe",
+    "stress",
+    "analyze",
+    "analyze_poem",
+    "themes",
+    "theme",
+];
+
+/// calls one of `METHODS` by name; the same for every transport
+pub fn call(state: &State, method: &str, params: &Value) -> Result<Value, String> {
+    let to_value = |v: Result<Value, serde_json::Error>| v.map_err(|err| err.to_string());
+    match method {
+        "find" => {
+            let r: FindRequest = parse(params)?;
+            let gs = settings(state, &r.settings)?;
+            let args = FindArgs {
+                theme: r.theme,
+                rps: r.rps,
+                top_n: r.top_n.unwrap_or(100),
+                sound_play: r.sound_play,
+                word_play: r.word_play,
+                ..FindArgs::new(&r.query)
+            };
+            let results = match args.word_play {
+                Some(_) => to_value(serde_json::to_value(word_play_from_args(
+                    &state.wc, &gs, &args,
+                )?))?,
+                None => to_value(serde_json::to_value(find_from_args(
+                    &state.wc, &state.mf, &gs, &args,
+                )?))?,
+            };
+            Ok(json!({ "results": results }))
+        }
+        "measure" => {
+            let r: MeasureRequest = parse(params)?;
+            let gs = settings(state, &r.settings)?;
+            let args = MeasureArgs {
+                to_find: r.to_find,
+                measured: r.measured,
+                theme: r.theme,
+            };
+            let result = measure_with(&state.wc, &state.mf, &gs, &args, |r| {
+                serde_json::to_value(r)
+            })?;
+            Ok(json!({ "result": to_value(result)? }))
+        }
+        "transcribe" => {
+            let r: TranscribeRequest = parse(params)?;
+            let words = r
+                .words
+                .iter()
+                .map(|w| string2word(&state.wc, w))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(json!({ "results": transcribe(&words, r.notation) }))
+        }
+        "stress" => {
+            let r: WordsRequest = parse(params)?;
+            let words = r
+                .words
+                .iter()
+                .map(|w| string2word(&state.wc, w))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(json!({ "results": stress(&r.words, &words) }))
+        }
+        "analyze" => {
+            let r: WordRequest = parse(params)?;
+            to_value(serde_json::to_value(analyze(&state.wc, &r.word)?))
+        }
+        "analyze_poem" => {
+            let r: PoemRequest = parse(params)?;
+            let gs = settings(state, &r.settings)?;
+            to_value(serde_json::to_value(analyze_poem(&state.wc, &gs, &r.text)))
+        }
+        "themes" => Ok(json!({ "themes": theme_names(&state.mf) })),
+        "theme" => {
+            let r: ThemeRequest = parse(params)?;
+            let words = theme_words(&state.mf, &r.name)?;
+            Ok(json!({ "theme": r.name, "words": words }))
+        }
+        _ => Err(format!("Unknown method: {}", method)),
+    }
+}
@@ -0,0 +1,165 @@
+/*
+Rust implementation of advanced ryhmes finder
+Copyright (C) 2022  Andrej Sitnikov (sitandr, andr-sitnikov@mail.ru)
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+Module that turns real-world input (copied from Wiktionary, typed with Latin letters, …)
+into the notation the rest of the crate understands: lowercase Cyrillic with ' and ` stress marks
+*/
+
+use crate::translator_ru::ALL_VOWELS;
+
+const COMBINING_ACUTE: char = '\u{301}';
+const COMBINING_GRAVE: char = '\u{300}';
+const COMBINING_BREVE: char = '\u{306}';
+const COMBINING_DIAERESIS: char = '\u{308}';
+
+/// stripped from both ends of the word; + is not here as it is a pattern symbol
+/// (for ?, ! and [ see `is_pattern` in the pattern module)
+pub(crate) const PUNCTUATION: [char; 18] = [
+    '.', ',', ';', ':', '?', '!', '…', '«', '»', '"', '“', '”', '„', '(', ')', '[', ']', '—',
+];
+
+/// Latin letters that look exactly like Cyrillic ones
+const HOMOGLYPHS: [(char, char); 22] = [
+    ('a', 'а'),
+    ('c', 'с'),
+    ('e', 'е'),
+    ('o', 'о'),
+    ('p', 'р'),
+    ('x', 'х'),
+    ('y', 'у'),
+    ('k', 'к'),
+    ('A', 'А'),
+    ('B', 'В'),
+    ('C', 'С'),
+    ('E', 'Е'),
+    ('H', 'Н'),
+    ('K', 'К'),
+    ('M', 'М'),
+    ('O', 'О'),
+    ('P', 'Р'),
+    ('T', 'Т'),
+    ('X', 'Х'),
+    ('Y', 'У'),
+    ('ë', 'ё'),
+    ('Ë', 'Ё'),
+];
+
+fn is_cyrillic(c: char) -> bool {
+    matches!(c, 'а'..='я' | 'А'..='Я' | 'ё' | 'Ё')
+}
+
+fn is_stress_mark(c: char) -> bool {
+    c == '\'' || c == '`'
+}
+
+/// combining marks become stress marks or are merged with the previous letter (и + ˘ = й, е + ¨ = ё)
+fn replace_combining(s: &str) -> Vec<char> {
+    let mut res: Vec<char> = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        match (c, res.last().copied()) {
+            (COMBINING_ACUTE | '´', _) => res.push('\''),
+            (COMBINING_GRAVE, _) => res.push('`'),
+            (COMBINING_BREVE, Some('и')) => *res.last_mut().unwrap() = 'й',
+            (COMBINING_BREVE, Some('И')) => *res.last_mut().unwrap() = 'Й',
+            (COMBINING_DIAERESIS, Some('е')) => *res.last_mut().unwrap() = 'ё',
+            (COMBINING_DIAERESIS, Some('Е')) => *res.last_mut().unwrap() = 'Ё',
+            ('\u{300}'..='\u{36f}', _) => {}
+            ('‐' | '‑' | '–', _) => res.push('-'),
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+/// «карАндаш» → «кара'ндаш»; the first letter and words in capitals are considered just capitalized
+fn uppercase_stress(w: &[char]) -> Vec<char> {
+    let all_upper = w.iter().all(|c| !c.is_lowercase());
+    let mut res = Vec::with_capacity(w.len() + 1);
+    for (i, &c) in w.iter().enumerate() {
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        res.push(lower);
+        let after_separator = i == 0 || w[i - 1] == '-';
+        if !all_upper
+            && !after_separator
+            && c.is_uppercase()
+            && lower != 'ё'
+            && ALL_VOWELS.contains(&lower)
+            && !w.get(i + 1).copied().is_some_and(is_stress_mark)
+        {
+            res.push('\'');
+        }
+    }
+    res
+}
+
+/// normalizes a single word (or hyphenated compound) of user input:
+/// strips punctuation around it, converts combining accents and uppercase vowels to stress marks,
+/// Latin lookalikes (if the word has Cyrillic letters) to Cyrillic and everything to lowercase
+pub fn normalize(s: &str) -> String {
+    let w = replace_combining(s.trim());
+    let start = w
+        .iter()
+        .position(|c| !PUNCTUATION.contains(c) && *c != '-')
+        .unwrap_or(w.len());
+    let end = w
+        .iter()
+        .rposition(|c| !PUNCTUATION.contains(c) && *c != '-')
+        .map_or(start, |i| i + 1);
+    let mut w = w[start..end].to_vec();
+
+    if w.iter().any(|&c| is_cyrillic(c)) {
+        for c in w.iter_mut() {
+            if let Some((_, cyrillic)) = HOMOGLYPHS.iter().find(|(latin, _)| latin == c) {
+                *c = *cyrillic;
+            }
+        }
+    }
+    uppercase_stress(&w).into_iter().collect()
+}
+
+#[cfg(test)]
+#[test]
+fn check_normalize() {
+    assert_eq!(normalize("кара'ндаш"), "кара'ндаш");
+    assert_eq!(normalize("карАндаш"), "кара'ндаш");
+    assert_eq!(normalize("КАРАНДАШ"), "карандаш");
+    assert_eq!(normalize("Облако"), "облако");
+    assert_eq!(normalize("ОблАко"), "обла'ко");
+    assert_eq!(normalize("карА'ндаш"), "кара'ндаш");
+    assert_eq!(normalize("ЁЖ"), "ёж");
+
+    // Wiktionary: combining acute, decomposed й and ё
+    assert_eq!(normalize("каранда\u{301}ш"), "каранда'ш");
+    assert_eq!(normalize("во\u{300}доро\u{301}д"), "во`доро'д");
+    assert_eq!(normalize("и\u{306}од"), "йод");
+    assert_eq!(normalize("е\u{308}ж"), "ёж");
+
+    // Latin a and o
+    assert_eq!(normalize("кaрaндaш"), "карандаш");
+    assert_eq!(normalize("Coк"), "сок");
+    assert_eq!(normalize("cop"), "cop");
+
+    assert_eq!(normalize("«кто-то»,"), "кто-то");
+    assert_eq!(normalize("кто‑нибудь"), "кто-нибудь");
+    assert_eq!(normalize("северо-зАпад"), "северо-за'пад");
+    assert_eq!(normalize("Кто-То"), "кто-то");
+    assert_eq!(normalize("  (слово?) "), "слово");
+    assert_eq!(normalize("дом!"), "дом");
+    assert_eq!(normalize("+!ко"), "+!ко");
+    assert_eq!(normalize("..."), "");
+}
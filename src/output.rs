@@ -0,0 +1,294 @@
+/*
+Rust implementation of advanced ryhmes finder
+Copyright (C) 2022  Andrej Sitnikov (sitandr, andr-sitnikov@mail.ru)
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+Module that renders results of commands for scripts: every command gives a list of records
+with the same fields (in the order of the struct), which is printed as
+  json  — one array of objects
+  jsonl — one object per line
+  yaml  — a list of mappings
+  csv, tsv, table — a header and one row per record; nested fields become «outer.inner»,
+                    lists are joined with spaces, missing values are empty
+*/
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Jsonl,
+    Csv,
+    Tsv,
+    Yaml,
+}
+
+pub fn render<T: Serialize>(records: &[T], format: OutputFormat) -> Result<String, String> {
+    match format {
+        OutputFormat::Json => serde_json::to_string_pretty(records).map_err(|e| e.to_string()),
+        OutputFormat::Yaml => serde_yaml::to_string(records).map_err(|e| e.to_string()),
+        OutputFormat::Jsonl => Ok(records
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?
+            .join("\n")),
+        OutputFormat::Csv | OutputFormat::Tsv | OutputFormat::Table => {
+            let rows = records
+                .iter()
+                .map(|r| serde_json::to_value(r).map(|v| flatten(v, format)))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?;
+            Ok(render_rows(&rows, format))
+        }
+    }
+}
+
+/// record as (column, cell) pairs
+fn flatten(value: Value, format: OutputFormat) -> Vec<(String, String)> {
+    let mut res = vec![];
+    match value {
+        Value::Object(map) => flatten_object("", map, format, &mut res),
+        other => res.push(("value".to_string(), cell(other, format))),
+    }
+    res
+}
+
+fn flatten_object(
+    prefix: &str,
+    map: Map<String, Value>,
+    format: OutputFormat,
+    res: &mut Vec<(String, String)>,
+) {
+    for (k, v) in map {
+        let key = format!("{}{}", prefix, k);
+        match v {
+            Value::Object(inner) => flatten_object(&format!("{}.", key), inner, format, res),
+            other => res.push((key, cell(other, format))),
+        }
+    }
+}
+
+fn cell(value: Value, format: OutputFormat) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s,
+        // tables are for people, three digits are enough
+        Value::Number(n) if format == OutputFormat::Table && !n.is_i64() && !n.is_u64() => {
+            format!("{:.3}", n.as_f64().unwrap_or_default())
+        }
+        Value::Array(items) => items
+            .into_iter()
+            .map(|v| cell(v, format))
+            .collect::<Vec<_>>()
+            .join(" "),
+        other => other.to_string(),
+    }
+}
+
+fn escape(s: &str, format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Csv if s.contains(['"', ',', '\n', '\r']) => {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        }
+        OutputFormat::Tsv => s.replace(['\t', '\n', '\r'], " "),
+        _ => s.to_string(),
+    }
+}
+
+fn render_rows(rows: &[Vec<(String, String)>], format: OutputFormat) -> String {
+    let header: Vec<&str> = match rows.first() {
+        Some(row) => row.iter().map(|(k, _)| &**k).collect(),
+        None => return String::new(),
+    };
+    let cells = |row: &Vec<(String, String)>| -> Vec<String> {
+        header
+            .iter()
+            .map(|h| {
+                row.iter()
+                    .find(|(k, _)| k == h)
+                    .map(|(_, v)| escape(v, format))
+                    .unwrap_or_default()
+            })
+            .collect()
+    };
+    let mut lines: Vec<Vec<String>> = vec![header.iter().map(|h| escape(h, format)).collect()];
+    lines.extend(rows.iter().map(cells));
+
+    match format {
+        OutputFormat::Table => {
+            let widths: Vec<usize> = (0..header.len())
+                .map(|i| {
+                    lines
+                        .iter()
+                        .map(|l| l[i].chars().count())
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect();
+            lines
+                .iter()
+                .map(|l| {
+                    l.iter()
+                        .zip(widths.iter())
+                        .map(|(c, w)| format!("{:<w$}", c, w = w))
+                        .collect::<Vec<_>>()
+                        .join("  ")
+                        .trim_end()
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        _ => {
+            let sep = if format == OutputFormat::Csv {
+                ","
+            } else {
+                "\t"
+            };
+            lines
+                .iter()
+                .map(|l| l.join(sep))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    }
+}
+
+/// records that come in parts: jsonl, yaml, csv and tsv are printed at once (csv and tsv
+/// with one header), json and table need every record and are printed in `finish`
+pub struct RecordStream {
+    format: OutputFormat,
+    header_printed: bool,
+    collected: Vec<Value>,
+}
+
+impl RecordStream {
+    pub fn new(format: OutputFormat) -> Self {
+        RecordStream {
+            format,
+            header_printed: false,
+            collected: vec![],
+        }
+    }
+
+    /// text to print now (may be empty)
+    pub fn push<T: Serialize>(&mut self, records: &[T]) -> Result<String, String> {
+        match self.format {
+            OutputFormat::Json | OutputFormat::Table => {
+                for r in records {
+                    self.collected
+                        .push(serde_json::to_value(r).map_err(|e| e.to_string())?);
+                }
+                Ok(String::new())
+            }
+            OutputFormat::Csv | OutputFormat::Tsv if self.header_printed => {
+                let text = render(records, self.format)?;
+                Ok(text
+                    .split_once('\n')
+                    .map(|(_, rows)| rows.to_string())
+                    .unwrap_or_default())
+            }
+            _ => {
+                let text = render(records, self.format)?;
+                self.header_printed |= !text.is_empty();
+                Ok(text)
+            }
+        }
+    }
+
+    pub fn finish(self) -> Result<String, String> {
+        match self.format {
+            OutputFormat::Json | OutputFormat::Table => render(&self.collected, self.format),
+            _ => Ok(String::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn check_render() {
+    #[derive(Serialize)]
+    struct Inner {
+        a: f32,
+        b: u32,
+    }
+    #[derive(Serialize)]
+    struct Record {
+        word: String,
+        forms: Vec<String>,
+        part: Option<String>,
+        inner: Inner,
+    }
+    let records = [
+        Record {
+            word: "кот".to_string(),
+            forms: vec!["кот".to_string(), "кота".to_string()],
+            part: Some("с".to_string()),
+            inner: Inner { a: 0.5, b: 1 },
+        },
+        Record {
+            word: "a,\"b\"".to_string(),
+            forms: vec![],
+            part: None,
+            inner: Inner { a: 1.25, b: 2 },
+        },
+    ];
+
+    assert_eq!(
+        render(&records, OutputFormat::Csv).unwrap(),
+        "word,forms,part,inner.a,inner.b\nкот,кот кота,с,0.5,1\n\"a,\"\"b\"\"\",,,1.25,2"
+    );
+    assert_eq!(
+        render(&records, OutputFormat::Tsv).unwrap().lines().next(),
+        Some("word\tforms\tpart\tinner.a\tinner.b")
+    );
+    assert_eq!(
+        render(&records, OutputFormat::Table).unwrap(),
+        "word   forms     part  inner.a  inner.b\n\
+         кот    кот кота  с     0.500    1\n\
+         a,\"b\"                  1.250    2"
+    );
+    let jsonl = render(&records, OutputFormat::Jsonl).unwrap();
+    assert_eq!(
+        jsonl.lines().next(),
+        Some(r#"{"word":"кот","forms":["кот","кота"],"part":"с","inner":{"a":0.5,"b":1}}"#)
+    );
+    let json: Value = serde_json::from_str(&render(&records, OutputFormat::Json).unwrap()).unwrap();
+    assert_eq!(json[1]["part"], Value::Null);
+    assert!(render(&records, OutputFormat::Yaml)
+        .unwrap()
+        .starts_with("- word: кот\n"));
+    assert_eq!(render::<Record>(&[], OutputFormat::Csv).unwrap(), "");
+
+    let mut stream = RecordStream::new(OutputFormat::Csv);
+    assert_eq!(stream.push::<Record>(&[]).unwrap(), "");
+    let first = stream.push(&records[..1]).unwrap();
+    let second = stream.push(&records[1..]).unwrap();
+    assert_eq!(
+        format!("{}\n{}", first, second),
+        render(&records, OutputFormat::Csv).unwrap()
+    );
+    let mut stream = RecordStream::new(OutputFormat::Json);
+    assert_eq!(stream.push(&records[..1]).unwrap(), "");
+    stream.push(&records[1..]).unwrap();
+    assert_eq!(
+        stream.finish().unwrap(),
+        render(&records, OutputFormat::Json).unwrap()
+    );
+}
@@ -0,0 +1,744 @@
+/*
+Rust implementation of advanced ryhmes finder
+Copyright (C) 2022  Andrej Sitnikov (sitandr, andr-sitnikov@mail.ru)
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+Module that parses search patterns and matches them against the sounds of words
+
+Pattern is matched against the whole word (use * to leave the start or the end free):
+  ко'т      sounds; unstressed vowels may be written as spelled or as heard («ко*» and «ка*» match «коро'ва»)
+  +         any syllable; +{2} exactly two, +{1,3} from one to three, +{2,} two or more
+  !         any syllable with the primary stress; !а — stressed syllable with «а»
+  [кгх]     one of the sounds; [^аоу] — any sound except these
+  ?         any single sound
+  *         any number of any sounds
+After a space, constraints may follow:
+  pos=с,п   only these parts of speech
+  syll=2..4 number of syllables (syll=3, syll=2.., syll=..4)
+  len=3..6  number of letters
+Voice and softness of consonants are not compared (so «д» matches both «д» and «т»).
+*/
+
+use std::fmt::Write;
+use std::ops::{Range, RangeInclusive};
+
+use crate::normalizer::PUNCTUATION;
+use crate::translator_ru::{Consonant, Vowel, ALL_VOWELS};
+use crate::translator_struct::{Accent, Phone, Word};
+
+/// symbols that make the input a pattern rather than a word
+const PATTERN_SYMBOLS: [char; 5] = ['+', '!', '*', '?', '['];
+
+/// sound without voice and softness
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundClass {
+    /// index in `Vowel::ALL`
+    Vowel(u8),
+    /// index in `Consonant::ALL`
+    Consonant(u8),
+}
+
+#[derive(Debug, Clone)]
+pub enum PatternNode {
+    /// sounds written literally; `text` is what was written
+    Sounds {
+        text: String,
+        phones: Vec<Phone>,
+    },
+    /// one sound from the set (or not from the set if negated)
+    Class {
+        sounds: Vec<SoundClass>,
+        negated: bool,
+    },
+    AnySound,
+    AnySounds,
+    /// from `min` to `max` syllables; `vowel` is the index in `Vowel::ALL` (only for the stressed one)
+    Syllables {
+        stressed: bool,
+        vowel: Option<u8>,
+        min: usize,
+        max: usize,
+    },
+}
+
+impl PatternNode {
+    /// number of vowels the node matches if it is always the same
+    fn vowel_count(&self) -> Option<usize> {
+        match self {
+            PatternNode::Sounds { phones, .. } => Some(
+                phones
+                    .iter()
+                    .filter(|p| matches!(p, Phone::Vowel(_)))
+                    .count(),
+            ),
+            PatternNode::Class {
+                sounds,
+                negated: false,
+            } => {
+                let vowels = sounds
+                    .iter()
+                    .filter(|s| matches!(s, SoundClass::Vowel(_)))
+                    .count();
+                match vowels {
+                    0 => Some(0),
+                    n if n == sounds.len() => Some(1),
+                    _ => None,
+                }
+            }
+            PatternNode::Syllables { min, max, .. } if min == max => Some(*min),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    pub nodes: Vec<PatternNode>,
+    /// allowed parts of speech (empty — any)
+    pub speech_parts: Vec<String>,
+    pub syllables: Option<RangeInclusive<usize>>,
+    pub letters: Option<RangeInclusive<usize>>,
+}
+
+/// whether the input should be parsed as a pattern;
+/// punctuation around a word («слово?», «(кто?)», «дом!») doesn't make it one,
+/// so a pattern of letters with ? or ! at the end needs a constraint: «ко? len=3»
+pub fn is_pattern(s: &str) -> bool {
+    let has_symbols = |s: &str| s.chars().any(|c| PATTERN_SYMBOLS.contains(&c));
+    let word = s
+        .trim()
+        .trim_end_matches(|c| PUNCTUATION.contains(&c))
+        .trim_start_matches(|c| PUNCTUATION.contains(&c) && !PATTERN_SYMBOLS.contains(&c));
+    has_symbols(if word.is_empty() { s } else { word })
+}
+
+fn sound_class(c: char) -> Option<SoundClass> {
+    let vowel = match c {
+        'а' | 'я' => Some('а'),
+        'о' | 'ё' => Some('о'),
+        'э' | 'е' => Some('э'),
+        'и' => Some('и'),
+        'ы' => Some('ы'),
+        'у' | 'ю' => Some('у'),
+        _ => None,
+    };
+    if let Some(v) = vowel {
+        return Vowel::ALL
+            .iter()
+            .position(|&x| x == v)
+            .map(|i| SoundClass::Vowel(i as u8));
+    }
+    let consonant = match c {
+        'б' => 'п',
+        'в' => 'ф',
+        'г' => 'к',
+        'д' => 'т',
+        'ж' | 'щ' => 'ш',
+        'з' => 'с',
+        other => other,
+    };
+    Consonant::ALL
+        .iter()
+        .position(|&x| x == consonant)
+        .map(|i| SoundClass::Consonant(i as u8))
+}
+
+fn is_letter(c: char) -> bool {
+    matches!(c, 'а'..='я' | 'ё')
+}
+
+impl SoundClass {
+    fn matches(&self, phone: &Phone) -> bool {
+        match (self, phone) {
+            (SoundClass::Vowel(l), Phone::Vowel(v)) => *l == v.letter,
+            (SoundClass::Consonant(l), Phone::Consonant(c)) => *l == c.letter,
+            _ => false,
+        }
+    }
+}
+
+/// whether unstressed `written` vowel may sound as `heard` (akanye, ikanye, after always hard ж, ш, ц)
+fn may_reduce_to(written: u8, heard: u8) -> bool {
+    let (written, heard) = (Vowel::ALL[written as usize], Vowel::ALL[heard as usize]);
+    written == heard
+        || matches!(
+            (written, heard),
+            ('о', 'а' | 'ы') | ('а', 'и' | 'ы') | ('э', 'и' | 'ы') | ('и', 'ы')
+        )
+}
+
+/// literal sound of the pattern; stressed vowel matches only stressed one,
+/// other vowels of the pattern are as written and match what they turn into in unstressed syllables of the word
+fn same_sound(pattern: &Phone, phone: &Phone) -> bool {
+    match (pattern, phone) {
+        (Phone::Vowel(p), Phone::Vowel(v)) => match (p.accent, v.accent) {
+            (Accent::Primary, Accent::Primary) => p.letter == v.letter,
+            (Accent::Primary, _) => false,
+            (_, Accent::NoAccent) => may_reduce_to(p.letter, v.letter),
+            _ => p.letter == v.letter,
+        },
+        (Phone::Consonant(p), Phone::Consonant(c)) => p.letter == c.letter,
+        _ => false,
+    }
+}
+
+/// sounds of a literal part with vowels not reduced: the part alone doesn't know
+/// where the stress of the word is, so reduction is checked against the word (see `same_sound`)
+fn literal_phones(text: &str) -> Vec<Phone> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut unreduced = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        unreduced.push(c);
+        if ALL_VOWELS.contains(&c) && !chars.get(i + 1).is_some_and(|&n| n == '\'' || n == '`') {
+            unreduced.push('`');
+        }
+    }
+    let accents = Word::new(text, false)
+        .vowels()
+        .map(|v| v.accent)
+        .collect::<Vec<_>>();
+    let mut phones = Word::new(&unreduced, false).phones().to_vec();
+    let vowels = phones.iter_mut().filter_map(|p| match p {
+        Phone::Vowel(v) => Some(v),
+        Phone::Consonant(_) => None,
+    });
+    for (vowel, accent) in vowels.zip(accents) {
+        vowel.accent = accent;
+    }
+    phones
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, pos: usize, message: &str) -> String {
+        format!(
+            "Bad pattern: {} (position {})\n  {}\n  {}^",
+            message,
+            pos + 1,
+            self.src,
+            " ".repeat(pos)
+        )
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+
+    /// `{2}`, `{1,3}`, `{2,}`, `{,3}` after «+»
+    fn repetition(&mut self) -> Result<(usize, usize), String> {
+        if self.peek() != Some('{') {
+            return Ok((1, 1));
+        }
+        let open = self.pos;
+        self.pos += 1;
+        let min = self.number();
+        let max = if self.peek() == Some(',') {
+            self.pos += 1;
+            self.number().unwrap_or(usize::MAX)
+        } else {
+            min.ok_or_else(|| self.error(self.pos, "expected number of syllables"))?
+        };
+        if self.peek() != Some('}') {
+            return Err(match self.peek() {
+                Some(c) => self.error(self.pos, &format!("unexpected «{}» in «{{…}}»", c)),
+                None => self.error(open, "unclosed «{»"),
+            });
+        }
+        self.pos += 1;
+        let min = min.unwrap_or(0);
+        if min > max {
+            return Err(self.error(open, &format!("{} is more than {}", min, max)));
+        }
+        Ok((min, max))
+    }
+
+    fn class(&mut self) -> Result<PatternNode, String> {
+        let open = self.pos;
+        self.pos += 1;
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut sounds = vec![];
+        loop {
+            match self.peek() {
+                None => return Err(self.error(open, "unclosed «[»")),
+                Some(']') => break,
+                Some('ц') => {
+                    return Err(self.error(self.pos, "«ц» is two sounds (тс), it can't be in «[…]»"))
+                }
+                Some(c) => match sound_class(c) {
+                    Some(s) => sounds.push(s),
+                    None => return Err(self.error(self.pos, &format!("«{}» is not a sound", c))),
+                },
+            }
+            self.pos += 1;
+        }
+        if sounds.is_empty() {
+            return Err(self.error(open, "empty «[]»"));
+        }
+        self.pos += 1;
+        Ok(PatternNode::Class { sounds, negated })
+    }
+
+    fn sounds(&mut self) -> Result<PatternNode, String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c == '\'' || c == '`' {
+                let previous = self.chars[self.pos - 1];
+                if self.pos == start
+                    || sound_class(previous).is_none_or(|s| !matches!(s, SoundClass::Vowel(_)))
+                {
+                    return Err(self.error(self.pos, "stress mark not after a vowel"));
+                }
+            } else if !is_letter(c) && c != '-' {
+                break;
+            }
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        let phones = literal_phones(&text);
+        Ok(PatternNode::Sounds { text, phones })
+    }
+
+    fn node(&mut self) -> Result<PatternNode, String> {
+        let c = self.peek().unwrap();
+        match c {
+            '+' => {
+                self.pos += 1;
+                let (min, max) = self.repetition()?;
+                Ok(PatternNode::Syllables {
+                    stressed: false,
+                    vowel: None,
+                    min,
+                    max,
+                })
+            }
+            '!' => {
+                self.pos += 1;
+                if self.peek() == Some('{') {
+                    return Err(self.error(self.pos, "stressed syllable can't be repeated"));
+                }
+                let vowel = match self.peek().and_then(sound_class) {
+                    Some(SoundClass::Vowel(v)) => {
+                        self.pos += 1;
+                        Some(v)
+                    }
+                    _ => None,
+                };
+                Ok(PatternNode::Syllables {
+                    stressed: true,
+                    vowel,
+                    min: 1,
+                    max: 1,
+                })
+            }
+            '?' => {
+                self.pos += 1;
+                Ok(PatternNode::AnySound)
+            }
+            '*' => {
+                self.pos += 1;
+                Ok(PatternNode::AnySounds)
+            }
+            '[' => self.class(),
+            c if is_letter(c) => self.sounds(),
+            '\'' | '`' => Err(self.error(self.pos, "stress mark not after a vowel")),
+            c => Err(self.error(self.pos, &format!("unexpected «{}»", c))),
+        }
+    }
+
+    /// `2..4`, `3`, `2..`, `..4`
+    fn range(&self, start: usize, value: &str) -> Result<RangeInclusive<usize>, String> {
+        let parse = |s: &str, default: usize| -> Result<usize, String> {
+            if s.is_empty() {
+                Ok(default)
+            } else {
+                s.parse()
+                    .map_err(|_| self.error(start, &format!("«{}» is not a number", s)))
+            }
+        };
+        match value.split_once("..") {
+            Some((min, max)) => Ok(parse(min, 0)?..=parse(max, usize::MAX)?),
+            None => {
+                let n = parse(value, 0)?;
+                Ok(n..=n)
+            }
+        }
+    }
+
+    fn constraint(&mut self, pattern: &mut Pattern) -> Result<(), String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !c.is_whitespace()) {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        let (key, value) = text.split_once('=').ok_or_else(|| {
+            self.error(
+                start,
+                "expected constraint like «pos=с», «syll=2..4» or «len=3..6»",
+            )
+        })?;
+        let value_start = start + key.chars().count() + 1;
+        match key {
+            "pos" => {
+                pattern.speech_parts = value.split(',').map(|s| s.to_string()).collect();
+            }
+            "syll" => pattern.syllables = Some(self.range(value_start, value)?),
+            "len" => pattern.letters = Some(self.range(value_start, value)?),
+            other => {
+                return Err(self.error(
+                    start,
+                    &format!("unknown constraint «{}» (expected pos, syll or len)", other),
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Pattern {
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            src,
+            chars: src.to_lowercase().chars().collect(),
+            pos: 0,
+        };
+        let mut pattern = Pattern {
+            nodes: vec![],
+            speech_parts: vec![],
+            syllables: None,
+            letters: None,
+        };
+        while parser.peek().is_some_and(|c| c.is_whitespace()) {
+            parser.pos += 1;
+        }
+        while parser.peek().is_some_and(|c| !c.is_whitespace()) {
+            let node = parser.node()?;
+            pattern.nodes.push(node);
+        }
+        if pattern.nodes.is_empty() {
+            return Err(parser.error(parser.pos, "empty pattern"));
+        }
+        loop {
+            while parser.peek().is_some_and(|c| c.is_whitespace()) {
+                parser.pos += 1;
+            }
+            if parser.peek().is_none() {
+                break;
+            }
+            parser.constraint(&mut pattern)?;
+        }
+        Ok(pattern)
+    }
+
+    pub fn allows_speech_part(&self, speech_part: &str) -> bool {
+        self.speech_parts.is_empty() || self.speech_parts.iter().any(|s| s == speech_part)
+    }
+
+    /// checks constraints and sounds (speech part is checked by the finder)
+    pub fn matches(&self, word: &Word) -> bool {
+        if let Some(syllables) = &self.syllables {
+            if !syllables.contains(&word.get_vowel_count()) {
+                return false;
+            }
+        }
+        if let Some(letters) = &self.letters {
+            if !letters.contains(&word.src.chars().count()) {
+                return false;
+            }
+        }
+        match_nodes(&self.nodes, word.phones())
+    }
+
+    /// word used to rank matched words: every wildcard becomes a placeholder
+    /// («+» for a syllable or a vowel, the first sound of a consonant class);
+    /// vowels are compared from the end, so everything before the last part with unknown number of vowels
+    /// («*», «?», «+{1,3}», a negated or mixed class) is dropped
+    pub fn representative(&self) -> Word {
+        let start = self
+            .nodes
+            .iter()
+            .rposition(|n| n.vowel_count().is_none())
+            .map_or(0, |i| i + 1);
+        let mut s = String::new();
+        for node in self.nodes[start..].iter() {
+            match node {
+                PatternNode::Sounds { text, .. } => s.push_str(text),
+                PatternNode::Class { sounds, .. } => match sounds[0] {
+                    SoundClass::Vowel(_) => s.push('+'),
+                    SoundClass::Consonant(c) => s.push(Consonant::ALL[c as usize]),
+                },
+                PatternNode::Syllables {
+                    stressed: true,
+                    vowel,
+                    ..
+                } => match vowel {
+                    Some(v) => {
+                        let _ = write!(s, "{}'", Vowel::ALL[*v as usize]);
+                    }
+                    None => s.push('!'),
+                },
+                PatternNode::Syllables { min, .. } => s.push_str(&"+".repeat(*min)),
+                PatternNode::AnySound | PatternNode::AnySounds => {}
+            }
+        }
+        Word::new(&s, false)
+    }
+
+    /// number of syllables after the stressed one if it doesn't depend on the word
+    /// (used to search only among words with such stress)
+    pub fn stress_from_end(&self) -> Option<usize> {
+        let stressed = self.nodes.iter().rposition(|n| match n {
+            PatternNode::Syllables { stressed, .. } => *stressed,
+            PatternNode::Sounds { phones, .. } => phones
+                .iter()
+                .any(|p| matches!(p, Phone::Vowel(v) if matches!(v.accent, Accent::Primary))),
+            _ => false,
+        })?;
+        let mut count = match &self.nodes[stressed] {
+            PatternNode::Sounds { phones, .. } => {
+                let vowels: Vec<&Phone> = phones
+                    .iter()
+                    .filter(|p| matches!(p, Phone::Vowel(_)))
+                    .collect();
+                let position = vowels.iter().rposition(
+                    |p| matches!(p, Phone::Vowel(v) if matches!(v.accent, Accent::Primary)),
+                )?;
+                vowels.len() - position - 1
+            }
+            _ => 0,
+        };
+        for node in self.nodes[stressed + 1..].iter() {
+            count += match node {
+                PatternNode::Sounds { phones, .. } => phones
+                    .iter()
+                    .filter(|p| matches!(p, Phone::Vowel(_)))
+                    .count(),
+                PatternNode::Syllables { min, max, .. } if min == max => *min,
+                PatternNode::Class {
+                    sounds,
+                    negated: false,
+                } if sounds.iter().all(|s| matches!(s, SoundClass::Consonant(_))) => 0,
+                _ => return None,
+            };
+        }
+        Some(count)
+    }
+}
+
+/// possible ends of a single syllable at the start of `phones`: consonants, the vowel, consonants
+fn syllable_ends(phones: &[Phone], stressed: bool, vowel: Option<u8>) -> Range<usize> {
+    let nucleus = match phones.iter().position(|p| matches!(p, Phone::Vowel(_))) {
+        Some(i) => i,
+        None => return 0..0,
+    };
+    let ok = match &phones[nucleus] {
+        Phone::Vowel(v) => {
+            (!stressed || matches!(v.accent, Accent::Primary))
+                && vowel.is_none_or(|l| l == v.letter)
+        }
+        Phone::Consonant(_) => false,
+    };
+    if !ok {
+        return 0..0;
+    }
+    let coda = phones[nucleus + 1..]
+        .iter()
+        .take_while(|p| matches!(p, Phone::Consonant(_)))
+        .count();
+    nucleus + 1..nucleus + coda + 2
+}
+
+/// backtracking over the nodes; patterns and words are short, so it is fast enough
+fn match_nodes(nodes: &[PatternNode], phones: &[Phone]) -> bool {
+    let (node, rest) = match nodes.split_first() {
+        Some(split) => split,
+        None => return phones.is_empty(),
+    };
+    match node {
+        PatternNode::Sounds { phones: sounds, .. } => {
+            phones.len() >= sounds.len()
+                && sounds.iter().zip(phones).all(|(p, w)| same_sound(p, w))
+                && match_nodes(rest, &phones[sounds.len()..])
+        }
+        PatternNode::Class { sounds, negated } => match phones.first() {
+            Some(p) => {
+                sounds.iter().any(|s| s.matches(p)) != *negated && match_nodes(rest, &phones[1..])
+            }
+            None => false,
+        },
+        PatternNode::AnySound => !phones.is_empty() && match_nodes(rest, &phones[1..]),
+        PatternNode::AnySounds => (0..=phones.len()).any(|i| match_nodes(rest, &phones[i..])),
+        PatternNode::Syllables {
+            stressed,
+            vowel,
+            min,
+            max,
+        } => {
+            if *min == 0 && match_nodes(rest, phones) {
+                return true;
+            }
+            if *max == 0 {
+                return false;
+            }
+            let remaining = PatternNode::Syllables {
+                stressed: *stressed,
+                vowel: *vowel,
+                min: min.saturating_sub(1),
+                max: max.saturating_sub(1),
+            };
+            syllable_ends(phones, *stressed, *vowel)
+                .any(|end| match_nodes_after(&remaining, rest, &phones[end..]))
+        }
+    }
+}
+
+/// matches `first` followed by `rest`
+fn match_nodes_after(first: &PatternNode, rest: &[PatternNode], phones: &[Phone]) -> bool {
+    let mut nodes = Vec::with_capacity(rest.len() + 1);
+    nodes.push(first.clone());
+    nodes.extend_from_slice(rest);
+    match_nodes(&nodes, phones)
+}
+
+#[cfg(test)]
+#[test]
+fn check_pattern() {
+    let matches = |p: &str, w: &str| Pattern::parse(p).unwrap().matches(&Word::new(w, false));
+
+    assert!(matches("+!ко", "доро'го"));
+    assert!(!matches("+!ко", "я'блоко"));
+    assert!(matches("!+ко", "я'блоко"));
+    assert!(!matches("+!ко", "молоко'"));
+    assert!(matches("+{1,3}!", "молоко'"));
+    assert!(!matches("+{1,3}!", "доро'га"));
+    assert!(matches("+{1,}!+", "доро'га"));
+    assert!(!matches("+{2,}!+", "доро'га"));
+    assert!(!matches("*!а*", "доро'га"));
+    assert!(matches("*!о*", "доро'га"));
+    assert!(matches("*!о+", "доро'га"));
+    assert!(matches("*ро'?а", "доро'га"));
+    assert!(matches("*ро'[кгх]а", "доро'га"));
+    assert!(!matches("*ро'[^кгх]а", "доро'га"));
+    assert!(matches("[дт]*", "доро'га"));
+    assert!(matches("*га", "доро'га"));
+    // voice is not compared, stressed vowel of the pattern needs stress in the word
+    assert!(matches("*ка", "доро'га"));
+    assert!(!matches("*а'", "доро'га"));
+    // unstressed literal vowels are reduced as in the word
+    assert!(matches("ко*", "коро'ва"));
+    assert!(matches("ка*", "коро'ва"));
+    assert!(matches("коро*", "коро'ва"));
+    assert!(matches("*ко", "я'блоко"));
+    assert!(matches("пя*", "пята'к"));
+    assert!(matches("моло*", "мо'лот"));
+    assert!(!matches("ку*", "коро'ва"));
+    assert!(!matches("ко'*", "коро'ва"));
+    assert!(matches("! syll=1", "ко'т"));
+    assert!(!matches("! syll=2..", "ко'т"));
+    assert!(matches("* len=..3", "ко'т"));
+    assert!(!matches("* len=4..", "ко'т"));
+
+    let pattern = Pattern::parse("+!ко pos=с,п").unwrap();
+    assert!(pattern.allows_speech_part("п"));
+    assert!(!pattern.allows_speech_part("г"));
+    assert_eq!(pattern.stress_from_end(), Some(1));
+    assert_eq!(pattern.representative().src, "+!ко");
+    let representative = |p: &str| Pattern::parse(p).unwrap().representative().stressed_src();
+    assert_eq!(representative("*!а"), "а'");
+    assert_eq!(representative("*ро'[кгх]а"), "ро'ка");
+    assert_eq!(representative("[ао]!ко"), "+!ко");
+    assert_eq!(representative("+{2}!"), "++!");
+    assert_eq!(representative("+{1,2}!ко"), "!ко");
+    assert_eq!(representative("ко'?а"), "а");
+    assert_eq!(representative("ко'*"), "");
+    assert_eq!(
+        Pattern::parse("*ро'[кг]а").unwrap().stress_from_end(),
+        Some(1)
+    );
+    assert_eq!(Pattern::parse("*!+{1,2}").unwrap().stress_from_end(), None);
+    assert_eq!(Pattern::parse("*!?").unwrap().stress_from_end(), None);
+    assert_eq!(Pattern::parse("+{2}").unwrap().stress_from_end(), None);
+
+    assert!(is_pattern("+!ко"));
+    assert!(!is_pattern("ко'т"));
+    assert!(!is_pattern("ко'т?"));
+    assert!(!is_pattern("(кто?)"));
+    assert!(!is_pattern("дом!"));
+    assert!(is_pattern("+!"));
+    assert!(is_pattern("!ко"));
+    assert!(is_pattern("[кг]от"));
+    assert!(is_pattern("ко[тд]"));
+    assert!(is_pattern("*ко?"));
+    assert!(is_pattern("ко? len=3"));
+    assert!(is_pattern("?"));
+
+    let error = |p: &str| Pattern::parse(p).unwrap_err();
+    assert_eq!(
+        error("+{2"),
+        "Bad pattern: unclosed «{» (position 2)\n  +{2\n   ^"
+    );
+    assert_eq!(
+        error("[кг"),
+        "Bad pattern: unclosed «[» (position 1)\n  [кг\n  ^"
+    );
+    assert_eq!(
+        error("+{3,1}").lines().next().unwrap(),
+        "Bad pattern: 3 is more than 1 (position 2)"
+    );
+    assert_eq!(
+        error("к'от").lines().next().unwrap(),
+        "Bad pattern: stress mark not after a vowel (position 2)"
+    );
+    assert_eq!(
+        error("+ko").lines().next().unwrap(),
+        "Bad pattern: unexpected «k» (position 2)"
+    );
+    assert_eq!(
+        error("+ pos").lines().next().unwrap(),
+        "Bad pattern: expected constraint like «pos=с», «syll=2..4» or «len=3..6» (position 3)"
+    );
+    assert_eq!(
+        error("+ syll=a").lines().next().unwrap(),
+        "Bad pattern: «a» is not a number (position 8)"
+    );
+    assert_eq!(
+        error("+ rhyme=1").lines().next().unwrap(),
+        "Bad pattern: unknown constraint «rhyme» (expected pos, syll or len) (position 3)"
+    );
+    assert_eq!(
+        error("[ц]").lines().next().unwrap(),
+        "Bad pattern: «ц» is two sounds (тс), it can't be in «[…]» (position 2)"
+    );
+}
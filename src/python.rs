@@ -0,0 +1,336 @@
+/*
+Rust implementation of advanced ryhmes finder
+Copyright (C) 2022  Andrej Sitnikov (sitandr, andr-sitnikov@mail.ru)
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+Module with Python bindings (feature `python`, built with `maturin build --release`):
+  wc = quickpoeter.WordCollector.load(".")
+  gs = quickpoeter.GeneralSettings.load(".", profile="slant", overrides=["stresses.weight=80"])
+  theme = wc.theme("Love")  # or quickpoeter.MeanTheme(wc, ["любовь", "сердце"])
+  quickpoeter.find(wc, "ко'т", gs, theme=theme, rps="г+н", top_n=10)  # list of dicts
+  quickpoeter.measure(wc, "ко'т", "кро'т", gs)                       # dict
+  quickpoeter.transcribe(wc, ["ко'т"], notation="ipa")                # list of dicts
+Results are the same records as `--format json` gives; errors are ValueError
+*/
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use pyo3::IntoPyObjectExt;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::api::{
+    find_with_theme, get_theme_by_key, load_settings, measure_with_theme, string2word, theme_names,
+    transcribe as transcribe_words, FindArgs, MeasureArgs,
+};
+use crate::finder::WordCollector as Collector;
+use crate::meaner::MeanTheme as Theme;
+use crate::reader::{GeneralSettings as Settings, MeanStrThemes};
+use crate::translator_ru::PhoneticAlphabet;
+
+fn value_error(s: String) -> PyErr {
+    PyValueError::new_err(s)
+}
+
+fn to_py(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    match value {
+        Value::Null => Ok(py.None()),
+        Value::Bool(b) => b.into_py_any(py),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into_py_any(py),
+            None => n.as_f64().unwrap_or_default().into_py_any(py),
+        },
+        Value::String(s) => s.into_py_any(py),
+        Value::Array(items) => {
+            let list = PyList::empty(py);
+            for v in items {
+                list.append(to_py(py, v)?)?;
+            }
+            list.into_py_any(py)
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (k, v) in map {
+                dict.set_item(k, to_py(py, v)?)?;
+            }
+            dict.into_py_any(py)
+        }
+    }
+}
+
+fn records<T: Serialize>(py: Python<'_>, records: &T) -> PyResult<PyObject> {
+    let value = serde_json::to_value(records).map_err(|e| value_error(e.to_string()))?;
+    to_py(py, &value)
+}
+
+/// loading panics on missing files, here it is an error
+fn loaded<T>(f: impl FnOnce() -> Result<T, String>) -> PyResult<T> {
+    catch_unwind(AssertUnwindSafe(f))
+        .map_err(|panic| {
+            let message = match panic.downcast::<String>() {
+                Ok(s) => *s,
+                Err(panic) => panic
+                    .downcast::<&str>()
+                    .map_or("Can't load".to_string(), |s| s.to_string()),
+            };
+            PyIOError::new_err(message)
+        })?
+        .map_err(value_error)
+}
+
+/// dictionary with word vectors and themes from config/themes.yaml
+#[pyclass(frozen, module = "quickpoeter")]
+pub struct WordCollector {
+    wc: Collector,
+    mf: MeanStrThemes,
+}
+
+#[pymethods]
+impl WordCollector {
+    /// loads `dir/res` and `dir/config/themes.yaml`
+    #[staticmethod]
+    #[pyo3(signature = (dir = "."))]
+    fn load(dir: &str) -> PyResult<Self> {
+        let dir = PathBuf::from(dir);
+        loaded(|| {
+            Ok(WordCollector {
+                wc: Collector::load_default(&dir),
+                mf: MeanStrThemes::load_default(&dir),
+            })
+        })
+    }
+
+    fn themes(&self) -> Vec<&str> {
+        theme_names(&self.mf)
+    }
+
+    /// theme from config/themes.yaml by name
+    fn theme(&self, name: String) -> PyResult<MeanTheme> {
+        match get_theme_by_key(&self.wc, &self.mf, Some(name)) {
+            Ok(Some(theme)) => Ok(MeanTheme { theme }),
+            Ok(None) => unreachable!("the key is given"),
+            Err(s) => Err(value_error(s)),
+        }
+    }
+
+    fn __len__(&self) -> usize {
+        self.wc.words().len()
+    }
+}
+
+/// coefficients from config/coefficients.yaml (with a profile and overrides)
+#[pyclass(frozen, module = "quickpoeter")]
+#[derive(Clone)]
+pub struct GeneralSettings {
+    gs: Settings,
+}
+
+#[pymethods]
+impl GeneralSettings {
+    /// `profile` is a name from config/profiles, `overrides` are like "stresses.weight=80"
+    #[staticmethod]
+    #[pyo3(signature = (dir = ".", profile = None, overrides = vec![]))]
+    fn load(dir: &str, profile: Option<&str>, overrides: Vec<String>) -> PyResult<Self> {
+        let dir = PathBuf::from(dir);
+        loaded(|| load_settings(&dir, profile, &overrides)).map(|gs| GeneralSettings { gs })
+    }
+
+    /// new settings with `overrides` like "stresses.weight=80"
+    fn with_overrides(&self, overrides: Vec<String>) -> PyResult<Self> {
+        self.gs
+            .clone()
+            .with_overrides(&overrides)
+            .map(|gs| GeneralSettings { gs })
+            .map_err(value_error)
+    }
+
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        records(py, &self.gs)
+    }
+}
+
+/// mean meaning of words: found words closer to it are better
+#[pyclass(frozen, module = "quickpoeter")]
+pub struct MeanTheme {
+    theme: Theme,
+}
+
+#[pymethods]
+impl MeanTheme {
+    /// theme from words of the dictionary
+    #[new]
+    fn new(wc: &WordCollector, words: Vec<String>) -> PyResult<Self> {
+        if words.is_empty() {
+            return Err(value_error("No words for the theme".to_string()));
+        }
+        Theme::from_str(&wc.wc, &words)
+            .map(|theme| MeanTheme { theme })
+            .map_err(|unknown| value_error(format!("Unknown words: {:?}", unknown)))
+    }
+}
+
+/// best rhymes for `query` (use ' to mind the stress) or a pattern, as dicts with all subdistances;
+/// `rps` are parts of speech to remove separated with "+"
+#[pyfunction]
+#[pyo3(signature = (wc, query, settings, theme = None, rps = None, top_n = 100))]
+fn find(
+    py: Python<'_>,
+    wc: &WordCollector,
+    query: &str,
+    settings: &GeneralSettings,
+    theme: Option<&MeanTheme>,
+    rps: Option<String>,
+    top_n: u32,
+) -> PyResult<PyObject> {
+    let args = FindArgs {
+        rps,
+        top_n,
+        ..FindArgs::new(query)
+    };
+    let found = find_with_theme(&wc.wc, &settings.gs, &args, theme.map(|t| &t.theme))
+        .map_err(value_error)?;
+    records(py, &found)
+}
+
+/// distance between two words with all subdistances
+#[pyfunction]
+#[pyo3(signature = (wc, to_find, measured, settings, theme = None))]
+fn measure(
+    py: Python<'_>,
+    wc: &WordCollector,
+    to_find: &str,
+    measured: &str,
+    settings: &GeneralSettings,
+    theme: Option<&MeanTheme>,
+) -> PyResult<PyObject> {
+    let args = MeasureArgs {
+        to_find: to_find.to_string(),
+        measured: measured.to_string(),
+        theme: None,
+    };
+    let theme = theme.map(|t| &t.theme);
+    let value = measure_with_theme(&wc.wc, &settings.gs, &args, theme, |r| {
+        serde_json::to_value(r)
+    })
+    .map_err(value_error)?
+    .map_err(|e| value_error(e.to_string()))?;
+    to_py(py, &value)
+}
+
+/// transcription of words (inner notation, "ipa" or "x-sampa")
+#[pyfunction]
+#[pyo3(signature = (wc, words, notation = None))]
+fn transcribe(
+    py: Python<'_>,
+    wc: &WordCollector,
+    words: Vec<String>,
+    notation: Option<&str>,
+) -> PyResult<PyObject> {
+    let notation = notation
+        .map(|n| clap::ValueEnum::from_str(n, true))
+        .transpose()
+        .map_err(|_| value_error("Notation is «ipa» or «x-sampa»".to_string()))?;
+    let words = words
+        .iter()
+        .map(|w| string2word(&wc.wc, w))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(value_error)?;
+    records(
+        py,
+        &transcribe_words(&words, notation as Option<PhoneticAlphabet>),
+    )
+}
+
+#[pymodule]
+fn quickpoeter(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<WordCollector>()?;
+    m.add_class::<GeneralSettings>()?;
+    m.add_class::<MeanTheme>()?;
+    m.add_function(wrap_pyfunction!(find, m)?)?;
+    m.add_function(wrap_pyfunction!(measure, m)?)?;
+    m.add_function(wrap_pyfunction!(transcribe, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+#[test]
+fn check_python() {
+    use crate::reader::VECTOR_DIM;
+    use std::ffi::CString;
+
+    let words = [
+        ("кот", "с+ко'т+;а"),
+        ("рот", "с+ро'т+"),
+        ("крот", "с+кро'т+"),
+        ("дом", "с+до'м+"),
+    ];
+    let i2w = words.map(|(k, _)| k.to_string()).to_vec();
+    let zaliz = words
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let wc = WordCollector {
+        wc: Collector::new(i2w, zaliz, vec![[0.0; VECTOR_DIM]; words.len()]),
+        mf: MeanStrThemes::default(),
+    };
+    let gs = GeneralSettings {
+        gs: Settings::try_load_default(&PathBuf::from(".")).unwrap(),
+    };
+
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let m = PyModule::new(py, "quickpoeter").unwrap();
+        quickpoeter(&m).unwrap();
+        let globals = PyDict::new(py);
+        globals.set_item("qp", m).unwrap();
+        globals.set_item("wc", Py::new(py, wc).unwrap()).unwrap();
+        globals.set_item("gs", Py::new(py, gs).unwrap()).unwrap();
+        let code = r#"
+found = qp.find(wc, "ко'т", gs, top_n=2)
+assert len(found) == 2 and found[0]["word"] == "кот", found
+assert set(found[0]) >= {"dist", "vowel", "cons", "struct", "sameSP"}
+
+heavy = gs.with_overrides(["stresses.weight=1000"])
+assert qp.measure(wc, "ко'т", "ро'т", heavy)["dist"] >= qp.measure(wc, "ко'т", "ро'т", gs)["dist"]
+assert gs.to_dict()["poem"]["window"] > 0
+
+assert qp.transcribe(wc, ["кот"], notation="ipa")[0]["word"] == "ко'т"
+theme = qp.MeanTheme(wc, ["кот", "дом"])
+assert qp.find(wc, "ко'т", gs, theme=theme, rps="с", top_n=5) == []
+assert len(wc) >= 4 and "Love" in wc.themes()
+
+for bad in [lambda: qp.find(wc, "'", gs), lambda: gs.with_overrides(["x.y=1"]),
+            lambda: qp.MeanTheme(wc, ["абвгд"]), lambda: wc.theme("Love")]:
+    try:
+        bad()
+        raise AssertionError("no error")
+    except ValueError:
+        pass
+try:
+    qp.WordCollector.load("/nonexistent")
+    raise AssertionError("no error")
+except IOError:
+    pass
+"#;
+        py.run(&CString::new(code).unwrap(), Some(&globals), None)
+            .map_err(|e| e.display(py))
+            .unwrap();
+    });
+}
@@ -24,10 +24,12 @@ use half::f16;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_pickle::de::DeOptions;
+use serde_yaml::Value;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
 
 pub const VECTOR_DIM: usize = 150;
 
@@ -39,18 +41,175 @@ pub struct MiscSettings {
     pub length_diff_fine: f32,
 }
 
+/// `table` is precomputed from the other fields when settings are loaded,
+/// so the features cost nothing at search time;
+/// call `update_table` if you change the fields manually
 #[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(try_from = "ConsonantFeatures")]
 pub struct ConsonantDistanceSettings {
-    pub map: [(f32, f32); 12],
+    /// (place, manner) of articulation for every consonant in `Consonant::ALL` order
+    pub features: [(f32, f32); 13],
+    pub weights: FeatureWeights,
     pub pow: f32,
     pub denominator: f32,
+    /// [first][second][voicing differs][palatalization differs]
+    #[serde(skip)]
+    table: [[[[f32; 2]; 2]; 13]; 13],
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct FeatureWeights {
+    pub place: f32,
+    pub manner: f32,
+    pub voicing: f32,
+    pub palatalization: f32,
+}
+
+/// the same as for consonants
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(try_from = "DistanceMap")]
 pub struct VowelDistanceSettings {
     pub map: [(f32, f32); 6],
     pub pow: f32,
     pub denominator: f32,
+    #[serde(skip)]
+    table: [[f32; 6]; 6],
+}
+
+/// what is actually stored in YAML
+#[derive(Deserialize)]
+struct DistanceMap {
+    map: Vec<(f32, f32)>,
+    pow: f32,
+    denominator: f32,
+}
+
+/// `map` is the old format: the same as for vowels, without «й»
+#[derive(Deserialize)]
+struct ConsonantFeatures {
+    features: Option<Vec<(f32, f32)>>,
+    weights: Option<FeatureWeights>,
+    map: Option<Vec<(f32, f32)>>,
+    pow: f32,
+    denominator: f32,
+}
+
+impl FeatureWeights {
+    /// the weights the old `map` format had
+    fn legacy() -> Self {
+        FeatureWeights {
+            place: 1.0,
+            manner: 1.0,
+            voicing: 0.5,
+            palatalization: 0.5,
+        }
+    }
+}
+
+fn to_array<const N: usize>(v: &[(f32, f32)], name: &str) -> Result<[(f32, f32); N], String> {
+    v.try_into().map_err(|_| {
+        format!(
+            "Distance {} should have {} entries, got {}",
+            name,
+            N,
+            v.len()
+        )
+    })
+}
+
+/// min(1.0, [|x1 - x2|^pow + |y1 - y2|^pow]/denominator)
+fn map_distance(map: &[(f32, f32)], i: usize, j: usize, pow: f32, denominator: f32) -> f32 {
+    let (x1, y1) = map[i];
+    let (x2, y2) = map[j];
+    1.0_f32.min(((x1 - x2).abs().powf(pow) + (y1 - y2).abs().powf(pow)) / denominator)
+}
+
+impl ConsonantDistanceSettings {
+    /// min(1.0, [w_place·|Δplace|^pow + w_manner·|Δmanner|^pow + w_voicing·[voicing differs]
+    /// + w_palatalization·[palatalization differs]]/denominator)
+    pub fn update_table(&mut self) {
+        let w = &self.weights;
+        for i in 0..13 {
+            for j in 0..13 {
+                let (place1, manner1) = self.features[i];
+                let (place2, manner2) = self.features[j];
+                let articulation = w.place * (place1 - place2).abs().powf(self.pow)
+                    + w.manner * (manner1 - manner2).abs().powf(self.pow);
+                for voicing in 0..2 {
+                    for palatalization in 0..2 {
+                        self.table[i][j][voicing][palatalization] = 1.0_f32.min(
+                            (articulation
+                                + w.voicing * voicing as f32
+                                + w.palatalization * palatalization as f32)
+                                / self.denominator,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// distance between consonants by their indexes in `Consonant::ALL`
+    /// and whether their voicing and palatalization differ
+    #[inline]
+    pub fn get(&self, first: u8, second: u8, voicing: bool, palatalization: bool) -> f32 {
+        self.table[first as usize][second as usize][voicing as usize][palatalization as usize]
+    }
+}
+
+impl TryFrom<ConsonantFeatures> for ConsonantDistanceSettings {
+    type Error = String;
+
+    fn try_from(raw: ConsonantFeatures) -> Result<Self, String> {
+        let mut sett = ConsonantDistanceSettings {
+            features: match (raw.features, raw.map) {
+                (Some(features), _) => to_array(&features, "features")?,
+                (None, Some(mut map)) => {
+                    // «й» is so far from everything that the distance to it is always 1.0, as it was
+                    map.push((1.0e6, 1.0e6));
+                    to_array(&map, "map")?
+                }
+                (None, None) => return Err("Consonant distance needs «features»".to_string()),
+            },
+            weights: raw.weights.unwrap_or_else(FeatureWeights::legacy),
+            pow: raw.pow,
+            denominator: raw.denominator,
+            table: [[[[0.0; 2]; 2]; 13]; 13],
+        };
+        sett.update_table();
+        Ok(sett)
+    }
+}
+
+impl VowelDistanceSettings {
+    pub fn update_table(&mut self) {
+        for i in 0..6 {
+            for j in 0..6 {
+                self.table[i][j] = map_distance(&self.map, i, j, self.pow, self.denominator);
+            }
+        }
+    }
+
+    /// distance between vowels by their indexes in `Vowel::ALL` (without «!» and «+»)
+    #[inline]
+    pub fn get(&self, first: u8, second: u8) -> f32 {
+        self.table[first as usize][second as usize]
+    }
+}
+
+impl TryFrom<DistanceMap> for VowelDistanceSettings {
+    type Error = String;
+
+    fn try_from(raw: DistanceMap) -> Result<Self, String> {
+        let mut sett = VowelDistanceSettings {
+            map: to_array(&raw.map, "map")?,
+            pow: raw.pow,
+            denominator: raw.denominator,
+            table: [[0.0; 6]; 6],
+        };
+        sett.update_table();
+        Ok(sett)
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -82,6 +241,8 @@ pub struct AlliterationSettings {
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ConsonantStructureSettings {
+    #[serde(default)]
+    pub by_syllables: bool,
     pub pow: f32,
     pub weight: f32,
     pub asympt: f32,
@@ -121,6 +282,58 @@ pub struct SamePartSpeechSettings {
     pub adv: f32,
 }
 
+/// sections below appeared later, so they have defaults (the same as in coefficients.yaml)
+/// and may be absent in old settings
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct SoundPlaySettings {
+    pub shift: f32,
+    pub pow: f32,
+    pub depth: usize,
+    pub stressed_vowel: f32,
+    pub weight: f32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct WordPlaySettings {
+    pub max_dist: f32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct PoemSettings {
+    pub rhyme_threshold: f32,
+    pub window: usize,
+}
+
+impl Default for SoundPlaySettings {
+    fn default() -> Self {
+        SoundPlaySettings {
+            shift: 1.0,
+            pow: 2.0,
+            depth: 4,
+            stressed_vowel: 2.0,
+            weight: 100.0,
+        }
+    }
+}
+
+impl Default for WordPlaySettings {
+    fn default() -> Self {
+        WordPlaySettings { max_dist: 1.5 }
+    }
+}
+
+impl Default for PoemSettings {
+    fn default() -> Self {
+        PoemSettings {
+            rhyme_threshold: 20.0,
+            window: 4,
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct GeneralSettings {
     pub misc: MiscSettings,
@@ -131,6 +344,12 @@ pub struct GeneralSettings {
     pub popularity: PopularitySettings,
     pub unsymmetrical: UnsymmetricalSettings,
     pub same_speech_part: SamePartSpeechSettings,
+    #[serde(default)]
+    pub sound_play: SoundPlaySettings,
+    #[serde(default)]
+    pub word_play: WordPlaySettings,
+    #[serde(default)]
+    pub poem: PoemSettings,
 }
 
 macro_rules! construct_path {
@@ -145,16 +364,138 @@ macro_rules! construct_path {
 }
 
 impl GeneralSettings {
-    /// Use instead of load_default if you don't want to increase binary size and load it externally
-    pub fn load_default(dir: &PathBuf) -> GeneralSettings {
-        yaml_read(construct_path!(dir, "config", "coefficients.yaml"))
-            .expect("Error reading default settings")
+    /// Use instead of default if you don't want to increase binary size and load it externally;
+    /// `dir` is the one with `config` folder
+    pub fn try_load_default(dir: &Path) -> Result<GeneralSettings, String> {
+        yaml_read(&dir.join("config").join("coefficients.yaml"))
+            .and_then(GeneralSettings::validated)
+            .map_err(|err| format!("Error reading default settings: {}", err))
     }
+
+    /// loads `config/coefficients.yaml` and puts the profile `config/profiles/<name>.yaml` over it;
+    /// profile may contain any subset of fields (e.g. only `stresses: {weight: 80}`)
+    pub fn load_profile(dir: &Path, name: &str) -> Result<GeneralSettings, String> {
+        // through GeneralSettings, so that the sections with defaults are there too
+        let base: GeneralSettings = yaml_read(&dir.join("config").join("coefficients.yaml"))?;
+        let mut base = serde_yaml::to_value(base).map_err(|err| err.to_string())?;
+        let mut path = profiles_dir(dir);
+        path.push(format!("{}.yaml", name));
+        let profile: Value = yaml_read(&path).map_err(|err| {
+            format!(
+                "Can't read profile «{}» ({}); available profiles: {}",
+                name,
+                err,
+                Self::list_profiles(dir).join(", ")
+            )
+        })?;
+        merge_yaml(&mut base, profile, "")
+            .map_err(|err| format!("Bad profile «{}»: {}", name, err))?;
+        serde_yaml::from_value::<GeneralSettings>(base)
+            .map_err(|err| err.to_string())?
+            .validated()
+    }
+
+    /// names of all profiles in `config/profiles`, sorted
+    pub fn list_profiles(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(profiles_dir(dir))
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path())
+                    .filter(|p| p.extension().is_some_and(|ext| ext == "yaml"))
+                    .filter_map(|p| Some(p.file_stem()?.to_str()?.to_owned()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    /// applies ad-hoc overrides like `stresses.weight=80` (value is parsed as YAML);
+    /// unknown paths are errors so that typos don't pass silently
+    pub fn with_overrides<S>(self, overrides: &[S]) -> Result<GeneralSettings, String>
+    where
+        S: Deref<Target = str>,
+    {
+        if overrides.is_empty() {
+            return Ok(self);
+        }
+        let mut tree = serde_yaml::to_value(&self).map_err(|err| err.to_string())?;
+        for o in overrides {
+            let (path, value) = o.split_once('=').ok_or(format!(
+                "Override «{}» should look like path.to.field=value",
+                &**o
+            ))?;
+            let value: Value = serde_yaml::from_str(value.trim())
+                .map_err(|err| format!("Bad value in override «{}»: {}", &**o, err))?;
+            set_yaml_path(&mut tree, path.trim(), value)?;
+        }
+        serde_yaml::from_value::<GeneralSettings>(tree)
+            .map_err(|err| err.to_string())?
+            .validated()
+    }
+}
+
+fn profiles_dir(dir: &Path) -> PathBuf {
+    dir.join("config").join("profiles")
+}
+
+/// puts `overlay` over `base`: mappings are merged key by key, everything else is replaced;
+/// keys missing from `base` are errors with their path (`path` is the path of `base`)
+fn merge_yaml(base: &mut Value, overlay: Value, path: &str) -> Result<(), String> {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (k, v) in overlay {
+                let key = match (path, k.as_str().unwrap_or("?")) {
+                    ("", key) => key.to_string(),
+                    (path, key) => format!("{}.{}", path, key),
+                };
+                let old = base
+                    .get_mut(&k)
+                    .ok_or_else(|| format!("Unknown settings path: {}", key))?;
+                merge_yaml(old, v, &key)?;
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+    Ok(())
+}
+
+/// sets value by dot-separated path (list items are adressed by number, e.g. `stresses.distance.map.0`)
+pub(crate) fn set_yaml_path(tree: &mut Value, path: &str, value: Value) -> Result<(), String> {
+    let mut current = tree;
+    for key in path.split('.') {
+        current = match current {
+            Value::Mapping(m) => m.get_mut(key),
+            Value::Sequence(s) => key.parse::<usize>().ok().and_then(|i| s.get_mut(i)),
+            _ => None,
+        }
+        .ok_or(format!("Unknown settings path: {}", path))?;
+    }
+    *current = value;
+    Ok(())
+}
+
+/// the same path format as in `set_yaml_path`
+pub(crate) fn get_yaml_path<'a>(tree: &'a Value, path: &str) -> Result<&'a Value, String> {
+    let mut current = tree;
+    for key in path.split('.') {
+        current = match current {
+            Value::Mapping(m) => m.get(key),
+            Value::Sequence(s) => key.parse::<usize>().ok().and_then(|i| s.get(i)),
+            _ => None,
+        }
+        .ok_or(format!("Unknown settings path: {}", path))?;
+    }
+    Ok(current)
 }
 
 impl Default for GeneralSettings {
     fn default() -> Self {
-        serde_yaml::from_slice(include_bytes!("../config/coefficients.yaml")).expect("Corrupt YAML file in build")
+        serde_yaml::from_slice::<GeneralSettings>(include_bytes!("../config/coefficients.yaml"))
+            .map_err(|err| err.to_string())
+            .and_then(GeneralSettings::validated)
+            .expect("Corrupt YAML file in build")
     }
 }
 
@@ -275,7 +616,7 @@ fn test_try_settings() {
         "{:?}",
         MeanStrThemes::load_default(&PathBuf::from(".")).str_themes["Art"]
     );
-    let gs = GeneralSettings::load_default(&PathBuf::from("."));
+    let gs = GeneralSettings::try_load_default(&PathBuf::from(".")).unwrap();
     let w1 = Word::new("сло'во", false);
     let w2 = Word::new("сла'ва", false);
     println!("слово-слава {:?}", w1.measure_distance(&w2, &gs));
@@ -286,3 +627,85 @@ fn test_try_settings() {
         w1.measure_distance(&w2, &gs)
     );
 }
+
+#[cfg(test)]
+#[test]
+fn test_profiles() {
+    let dir = PathBuf::from(".");
+    let base = GeneralSettings::try_load_default(&dir).unwrap();
+    for name in GeneralSettings::list_profiles(&dir) {
+        GeneralSettings::load_profile(&dir, &name).expect("Corrupt profile");
+    }
+
+    // only overriden fields change
+    let slant = GeneralSettings::load_profile(&dir, "slant").unwrap();
+    assert!(!slant.stresses.indexation);
+    assert_eq!(slant.stresses.asympt, base.stresses.asympt);
+    assert_eq!(slant.popularity.weight, base.popularity.weight);
+    assert!(GeneralSettings::load_profile(&dir, "no such profile").is_err());
+
+    // typos in profiles are errors as in overrides
+    let mut tree = serde_yaml::to_value(&base).unwrap();
+    let typo = serde_yaml::from_str("stresses: {wieght: 80}").unwrap();
+    assert_eq!(
+        merge_yaml(&mut tree, typo, ""),
+        Err("Unknown settings path: stresses.wieght".to_string())
+    );
+    let fine = serde_yaml::from_str("stresses: {weight: 80}").unwrap();
+    assert!(merge_yaml(&mut tree, fine, "").is_ok());
+    assert_eq!(tree["stresses"]["weight"], Value::from(80));
+
+    let gs = base
+        .clone()
+        .with_overrides(&["stresses.weight=80", "stresses.distance.map.0=[1, 2]"])
+        .unwrap();
+    assert_eq!(gs.stresses.weight, 80.0);
+    assert_eq!(gs.stresses.distance.map[0], (1.0, 2.0));
+    assert_eq!(gs.alliteration.weight, base.alliteration.weight);
+
+    assert!(base
+        .clone()
+        .with_overrides(&["stresses.wieght=80"])
+        .is_err());
+    assert!(base.clone().with_overrides(&["stresses.weight"]).is_err());
+    assert!(base.with_overrides(&["stresses.weight=abc"]).is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn test_legacy_settings() {
+    let old: ConsonantDistanceSettings = serde_yaml::from_str(
+        "{map: [[0, 1], [0.5, 1], [1, 1], [1.5, 1], [3, 1], [4, 0], [4, 2], [5, 0], [5, 2], [6, 1], [8, 0], [3, 0]], pow: 0.5, denominator: 3.0}",
+    )
+    .unwrap();
+    assert_eq!(old.features[0], (0.0, 1.0));
+    assert_eq!(old.get(0, 0, false, false), 0.0);
+    assert_eq!(old.get(0, 12, false, false), 1.0);
+    assert_eq!(
+        old.get(0, 1, true, false),
+        old.get(0, 1, false, false) + 0.5 / 3.0
+    );
+    assert!(
+        serde_yaml::from_str::<ConsonantDistanceSettings>("{pow: 0.5, denominator: 3.0}").is_err()
+    );
+
+    // coefficients.yaml from before by_syllables, sound_play, word_play and poem
+    let base = GeneralSettings::default();
+    let mut tree = serde_yaml::to_value(&base).unwrap();
+    let Value::Mapping(sections) = &mut tree else {
+        panic!("Settings are not a mapping")
+    };
+    for section in ["sound_play", "word_play", "poem"] {
+        sections.remove(section);
+    }
+    sections["consonant_structure"]
+        .as_mapping_mut()
+        .unwrap()
+        .remove("by_syllables");
+    let old: GeneralSettings = serde_yaml::from_value(tree).unwrap();
+    let old = old.validated().unwrap();
+    assert_eq!(
+        serde_yaml::to_value(&old).unwrap(),
+        serde_yaml::to_value(&base).unwrap()
+    );
+}
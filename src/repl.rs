@@ -0,0 +1,375 @@
+/*
+Rust implementation of advanced ryhmes finder
+Copyright (C) 2022  Andrej Sitnikov (sitandr, andr-sitnikov@mail.ru)
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+Module with interactive session (`quickpoeter repl`): the dictionary is loaded once,
+settings, theme and ignored parts of speech are kept between queries.
+Tab completes commands, theme names and dictionary words, history is kept in a file if it is given
+*/
+
+use std::path::Path;
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+use crate::api::{
+    found_records, measure, search_from_args, show_found, theme_names, FindArgs, MeasureArgs,
+};
+use crate::finder::{SearchSession, WordCollector};
+use crate::output::{render, OutputFormat};
+use crate::reader::{GeneralSettings, MeanStrThemes};
+
+pub const COMMANDS: [&str; 10] = [
+    "find", "more", "measure", "theme", "ignore", "set", "page", "help", "quit", "exit",
+];
+
+const HELP: &str = "\
+сло'во, find сло'во     rhymes (the first page); use find for words that are commands
+more                   the next page of the last search
+measure сло'во осно'ва  distance between words with all subdistances
+theme Love             choose the theme, «theme» without a name resets it
+ignore г н             ignore parts of speech, «ignore» without them resets it
+set stresses.weight 80 change a setting (the same as --set)
+page 20                number of words on a page
+help, quit";
+
+/// dictionary words shown on Tab
+const COMPLETIONS: usize = 50;
+
+pub struct Session<'a> {
+    wc: &'a WordCollector,
+    mf: &'a MeanStrThemes,
+    gs: GeneralSettings,
+    format: Option<OutputFormat>,
+    theme: Option<String>,
+    ignore: Vec<String>,
+    page: u32,
+    /// the last search and how many words of it are shown
+    last: Option<(FindArgs, SearchSession<'a>, usize)>,
+}
+
+impl<'a> Session<'a> {
+    pub fn new(
+        wc: &'a WordCollector,
+        mf: &'a MeanStrThemes,
+        gs: GeneralSettings,
+        format: Option<OutputFormat>,
+        page: u32,
+    ) -> Self {
+        Session {
+            wc,
+            mf,
+            gs,
+            format,
+            theme: None,
+            ignore: vec![],
+            page,
+            last: None,
+        }
+    }
+
+    /// output of the command; None if the session should end
+    pub fn execute(&mut self, line: &str) -> Option<Result<String, String>> {
+        let line = line.trim();
+        let (command, rest) = match line.split_once(char::is_whitespace) {
+            Some((command, rest)) => (command, rest.trim()),
+            None => (line, ""),
+        };
+        Some(match command {
+            "" => Ok(String::new()),
+            "quit" | "exit" => return None,
+            "help" => Ok(HELP.to_string()),
+            "find" => self.find(rest),
+            "more" => self.more(),
+            "measure" => self.measure(rest),
+            "theme" => self.set_theme(rest),
+            "ignore" => {
+                self.ignore = rest.split_whitespace().map(|s| s.to_string()).collect();
+                Ok(match self.ignore.is_empty() {
+                    true => "Nothing is ignored".to_string(),
+                    false => format!("Ignored: {}", self.ignore.join(", ")),
+                })
+            }
+            "set" => self.set(rest),
+            "page" => match rest.parse() {
+                Ok(page) if page > 0 => {
+                    self.page = page;
+                    Ok(format!("{} words on a page", page))
+                }
+                _ => Err(format!("Bad page size: «{}»", rest)),
+            },
+            _ => self.find(line),
+        })
+    }
+
+    fn find_args(&self, query: &str, top_n: u32) -> FindArgs {
+        FindArgs {
+            theme: self.theme.clone(),
+            rps: (!self.ignore.is_empty()).then(|| self.ignore.join("+")),
+            top_n,
+            ..FindArgs::new(query)
+        }
+    }
+
+    fn find(&mut self, query: &str) -> Result<String, String> {
+        if query.is_empty() {
+            return Err("Nothing to find".to_string());
+        }
+        let args = self.find_args(query, self.page);
+        let session = search_from_args(self.wc, self.mf, &self.gs, &args)?;
+        self.last = Some((args, session, 0));
+        self.more()
+    }
+
+    /// the next page of the last search (distances are not computed again)
+    fn more(&mut self) -> Result<String, String> {
+        let (args, session, shown) = self
+            .last
+            .as_mut()
+            .ok_or("Nothing to continue, find something first")?;
+        let new = session.page(*shown, self.page as usize);
+        if new.is_empty() {
+            return Ok("No more words".to_string());
+        }
+        *shown += new.len();
+        match self.format {
+            Some(f) => render(&found_records(new, args)?, f),
+            None => Ok(show_found(new, args)),
+        }
+    }
+
+    fn measure(&self, rest: &str) -> Result<String, String> {
+        let words: Vec<&str> = rest.split_whitespace().collect();
+        let [to_find, measured] = words[..] else {
+            return Err("Usage: measure сло'во осно'ва".to_string());
+        };
+        let args = MeasureArgs {
+            to_find: to_find.to_string(),
+            measured: measured.to_string(),
+            theme: self.theme.clone(),
+        };
+        measure(self.wc, self.mf, &self.gs, &args, self.format)
+    }
+
+    fn set_theme(&mut self, name: &str) -> Result<String, String> {
+        if name.is_empty() {
+            self.theme = None;
+            return Ok("No theme".to_string());
+        }
+        if !self.mf.str_themes.contains_key(name) {
+            return Err(format!("Unknown theme: {}", name));
+        }
+        self.theme = Some(name.to_string());
+        Ok(format!("Theme: {}", name))
+    }
+
+    /// «set path value» or «set path=value»
+    fn set(&mut self, rest: &str) -> Result<String, String> {
+        let (path, value) = rest
+            .split_once('=')
+            .or_else(|| rest.split_once(char::is_whitespace))
+            .ok_or("Usage: set stresses.weight 80")?;
+        let (path, value) = (path.trim(), value.trim());
+        self.gs = self
+            .gs
+            .clone()
+            .with_overrides(&[format!("{}={}", path, value)])?;
+        Ok(format!("{} = {}", path, value))
+    }
+}
+
+/// start of the completed word and candidates for the line before the cursor
+pub fn complete<'a>(
+    wc: &'a WordCollector,
+    themes: &[&'a str],
+    line: &str,
+) -> (usize, Vec<&'a str>) {
+    let start = line
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map(|(i, c)| i + c.len_utf8())
+        .unwrap_or(0);
+    let prefix = &line[start..];
+    let candidates = match line[..start].split_whitespace().next() {
+        None => COMMANDS
+            .iter()
+            .copied()
+            .filter(|c| c.starts_with(prefix))
+            .chain(wc.words_with_prefix(&prefix.to_lowercase(), COMPLETIONS))
+            .collect(),
+        Some("theme") => themes
+            .iter()
+            .copied()
+            .filter(|t| t.starts_with(prefix))
+            .collect(),
+        Some("set" | "page" | "ignore") => vec![],
+        Some(_) => wc.words_with_prefix(&prefix.to_lowercase(), COMPLETIONS),
+    };
+    (start, candidates)
+}
+
+struct ReplHelper<'a> {
+    wc: &'a WordCollector,
+    themes: Vec<&'a str>,
+}
+
+impl Completer for ReplHelper<'_> {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let (start, candidates) = complete(self.wc, &self.themes, &line[..pos]);
+        Ok((
+            start,
+            candidates.into_iter().map(|s| s.to_string()).collect(),
+        ))
+    }
+}
+
+impl Hinter for ReplHelper<'_> {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper<'_> {}
+
+impl Validator for ReplHelper<'_> {}
+
+impl Helper for ReplHelper<'_> {}
+
+/// runs the session until «quit» or Ctrl-D
+pub fn run_repl(
+    wc: &WordCollector,
+    mf: &MeanStrThemes,
+    gs: GeneralSettings,
+    format: Option<OutputFormat>,
+    page: u32,
+    history: Option<&Path>,
+) -> Result<(), String> {
+    let mut editor: Editor<ReplHelper, DefaultHistory> =
+        Editor::new().map_err(|err| err.to_string())?;
+    editor.set_helper(Some(ReplHelper {
+        wc,
+        themes: theme_names(mf),
+    }));
+    if let Some(path) = history {
+        // there is no history before the first session
+        let _ = editor.load_history(path);
+    }
+
+    let mut session = Session::new(wc, mf, gs, format, page);
+    println!("Type «help» for commands");
+    loop {
+        match editor.readline("> ") {
+            Ok(line) => {
+                let _ = editor.add_history_entry(line.as_str());
+                match session.execute(&line) {
+                    None => break,
+                    Some(Ok(out)) if out.is_empty() => {}
+                    Some(Ok(out)) => println!("{}", out),
+                    Some(Err(s)) => eprintln!("{}", s),
+                }
+            }
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+
+    if let Some(path) = history {
+        editor
+            .save_history(path)
+            .map_err(|err| format!("Can't write history to {:?}: {}", path, err))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[test]
+fn check_repl() {
+    use crate::reader::VECTOR_DIM;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    let gs = GeneralSettings::try_load_default(&PathBuf::from(".")).unwrap();
+    let mf = MeanStrThemes {
+        str_themes: HashMap::from([("Animals".to_string(), vec!["кот".to_string()])]),
+    };
+    let words = [
+        ("кот", "с+ко'т+;а"),
+        ("рот", "с+ро'т+"),
+        ("крот", "с+кро'т+"),
+        ("котёл", "с+котё'л+"),
+    ];
+    let i2w = words.map(|(k, _)| k.to_string()).to_vec();
+    let zaliz = words
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let wc = WordCollector::new(i2w, zaliz, vec![[0.0; VECTOR_DIM]; words.len()]);
+
+    let mut s = Session::new(&wc, &mf, gs, None, 2);
+    let mut run = |line: &str| s.execute(line).unwrap();
+
+    assert!(run("more").is_err());
+    let shown = |out: String| -> Vec<String> {
+        out.split('"')
+            .skip(1)
+            .step_by(2)
+            .map(|s| s.to_string())
+            .collect()
+    };
+    let first = shown(run("ко'т").unwrap());
+    let second = shown(run("more").unwrap());
+    assert_eq!(first.len(), 2);
+    assert!(!second.is_empty() && second.iter().all(|w| !first.contains(w)));
+    assert_eq!(run("page 10"), Ok("10 words on a page".to_string()));
+    assert_eq!(run("more"), Ok("No more words".to_string()));
+    assert!(run("page 0").is_err());
+
+    assert!(run("theme Plants").is_err());
+    assert_eq!(run("theme Animals"), Ok("Theme: Animals".to_string()));
+    assert_eq!(run("theme"), Ok("No theme".to_string()));
+    assert!(run("set stresses.weight 80").is_ok());
+    assert!(run("set stresses.wieght=80").is_err());
+    assert!(run("measure ко'т").is_err());
+    assert!(run("measure ко'т ро'т").unwrap().contains("dist"));
+
+    assert!(run("find ко'т").unwrap().contains("крот"));
+    assert_eq!(run("ignore с"), Ok("Ignored: с".to_string()));
+    assert!(!run("find ко'т").unwrap().contains("крот"));
+    assert_eq!(run("ignore"), Ok("Nothing is ignored".to_string()));
+    assert!(s.execute("quit").is_none());
+
+    let themes = theme_names(&mf);
+    assert_eq!(complete(&wc, &themes, "me"), (0, vec!["measure"]));
+    assert_eq!(
+        complete(&wc, &themes, "ко"),
+        (0, vec!["кот", "кота", "котёл"])
+    );
+    assert_eq!(complete(&wc, &themes, "theme An"), (6, vec!["Animals"]));
+    assert_eq!(complete(&wc, &themes, "measure кот кр"), (15, vec!["крот"]));
+}
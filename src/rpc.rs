@@ -0,0 +1,313 @@
+/*
+Rust implementation of advanced ryhmes finder
+Copyright (C) 2022  Andrej Sitnikov (sitandr, andr-sitnikov@mail.ru)
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+Module with JSON-RPC 2.0 over stdin/stdout for editors (`quickpoeter rpc`), the dictionary is loaded once.
+Messages are either framed with «Content-Length: N» headers (as in LSP) or are single lines of JSON;
+the answer is framed the same way as the request.
+Methods and parameters are the same as for the HTTP server (see the methods module):
+find, measure, transcribe, stress, analyze, analyze_poem, themes, theme.
+  $/cancelRequest {"id": 1} — the request is answered with error -32800 at once, its result is dropped
+  shutdown                 — answered with null
+  exit                     — ends the session (as well as the end of input)
+Requests are processed in `jobs` threads, so answers may come in other order;
+errors of methods, internal ones (panics) included, are answered with code -32000
+*/
+
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+use serde_json::{json, Value};
+
+use crate::methods::{call, State, METHODS};
+
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const SERVER_ERROR: i32 = -32000;
+const REQUEST_CANCELLED: i32 = -32800;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Framing {
+    Headers,
+    Line,
+}
+
+/// the next message; None at the end of input
+fn read_message(input: &mut impl BufRead) -> Result<Option<(Framing, String)>, String> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            return Ok(None);
+        }
+        if !line.trim().is_empty() {
+            break;
+        }
+    }
+    let length = match line.split_once(':') {
+        Some((name, value)) if name.trim().eq_ignore_ascii_case("content-length") => value
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| format!("Bad Content-Length: {}", value.trim()))?,
+        _ => return Ok(Some((Framing::Line, line.trim().to_string()))),
+    };
+    // other headers up to the empty line
+    loop {
+        line.clear();
+        if input.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            return Ok(None);
+        }
+        if line.trim().is_empty() {
+            break;
+        }
+    }
+    let mut body = vec![0; length];
+    input.read_exact(&mut body).map_err(|e| e.to_string())?;
+    String::from_utf8(body)
+        .map(|body| Some((Framing::Headers, body)))
+        .map_err(|e| e.to_string())
+}
+
+fn write_message(output: &mut impl Write, framing: Framing, message: &Value) -> Result<(), String> {
+    let text = message.to_string();
+    match framing {
+        Framing::Headers => write!(output, "Content-Length: {}\r\n\r\n{}", text.len(), text),
+        Framing::Line => writeln!(output, "{}", text),
+    }
+    .and_then(|_| output.flush())
+    .map_err(|e| e.to_string())
+}
+
+fn error(id: &Value, code: i32, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+/// a panic becomes an error, so that the worker thread stays alive
+fn catch_panic<T>(f: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|panic| {
+        let message = match panic.downcast::<String>() {
+            Ok(s) => *s,
+            Err(panic) => panic
+                .downcast::<&str>()
+                .map_or("Unknown panic".to_string(), |s| s.to_string()),
+        };
+        Err(format!("Internal error: {}", message))
+    })
+}
+
+struct Job {
+    id: Value,
+    method: String,
+    params: Value,
+    framing: Framing,
+}
+
+/// answers requests from `input` until «exit» or the end of input
+pub fn run_rpc(
+    state: &State,
+    mut input: impl BufRead,
+    output: impl Write + Send,
+    jobs: usize,
+) -> Result<(), String> {
+    let output = Mutex::new(output);
+    // ids (as JSON text) of requests that are not answered yet
+    let pending: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+    let send = |framing, message: &Value| {
+        if let Err(s) = write_message(&mut *output.lock().unwrap(), framing, message) {
+            eprintln!("{}", s);
+        }
+    };
+    // answers only if the request is not cancelled yet
+    let answer = |id: &Value, framing, message: &Value| {
+        if pending.lock().unwrap().remove(&id.to_string()) {
+            send(framing, message);
+        }
+    };
+
+    let (tx, rx) = mpsc::channel::<Job>();
+    let rx = Mutex::new(rx);
+    thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            scope.spawn(|| loop {
+                let next = rx.lock().unwrap().recv();
+                let Ok(job) = next else { break };
+                if !pending.lock().unwrap().contains(&job.id.to_string()) {
+                    continue;
+                }
+                let message = match catch_panic(|| call(state, &job.method, &job.params)) {
+                    Ok(result) => json!({"jsonrpc": "2.0", "id": job.id, "result": result}),
+                    Err(s) => error(&job.id, SERVER_ERROR, &s),
+                };
+                answer(&job.id, job.framing, &message);
+            });
+        }
+
+        let res = loop {
+            let (framing, text) = match read_message(&mut input) {
+                Ok(Some(message)) => message,
+                Ok(None) => break Ok(()),
+                Err(s) => break Err(s),
+            };
+            let request: Value = match serde_json::from_str(&text) {
+                Ok(request) => request,
+                Err(err) => {
+                    send(framing, &error(&Value::Null, PARSE_ERROR, &err.to_string()));
+                    continue;
+                }
+            };
+            let id = request.get("id").cloned();
+            let Some(method) = request.get("method").and_then(Value::as_str) else {
+                send(
+                    framing,
+                    &error(&id.unwrap_or_default(), INVALID_REQUEST, "No method"),
+                );
+                continue;
+            };
+            let params = request.get("params").cloned().unwrap_or(json!({}));
+            match (method, id) {
+                ("exit", _) => break Ok(()),
+                ("$/cancelRequest", _) => {
+                    let cancelled = params.get("id").cloned().unwrap_or_default();
+                    let message = error(&cancelled, REQUEST_CANCELLED, "Request cancelled");
+                    answer(&cancelled, framing, &message);
+                }
+                ("shutdown", Some(id)) => send(
+                    framing,
+                    &json!({"jsonrpc": "2.0", "id": id, "result": null}),
+                ),
+                (m, Some(id)) if METHODS.contains(&m) => {
+                    pending.lock().unwrap().insert(id.to_string());
+                    let job = Job {
+                        id,
+                        method: m.to_string(),
+                        params,
+                        framing,
+                    };
+                    tx.send(job)
+                        .expect("workers can't stop before the input ends");
+                }
+                (m, Some(id)) => send(
+                    framing,
+                    &error(&id, METHOD_NOT_FOUND, &format!("Unknown method: {}", m)),
+                ),
+                // notifications are not answered
+                (_, None) => {}
+            }
+        };
+        drop(tx);
+        res
+    })
+}
+
+#[cfg(test)]
+#[test]
+fn check_rpc() {
+    use crate::finder::WordCollector;
+    use crate::reader::{GeneralSettings, MeanStrThemes, VECTOR_DIM};
+    use std::path::PathBuf;
+
+    let words = [
+        ("кот", "с+ко'т+;а"),
+        ("рот", "с+ро'т+"),
+        ("крот", "с+кро'т+"),
+        ("дом", "с+до'м+"),
+        ("идти", "г+идти'+"),
+    ];
+    let i2w = words.map(|(k, _)| k.to_string()).to_vec();
+    let zaliz = words
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let state = State {
+        wc: WordCollector::new(i2w, zaliz, vec![[0.0; VECTOR_DIM]; words.len()]),
+        mf: MeanStrThemes::default(),
+        gs: GeneralSettings::try_load_default(&PathBuf::from(".")).unwrap(),
+    };
+
+    let framed = |body: &str| format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+    let input = [
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "find", "params": {"query": "ко'т", "top_n": 1}}"#
+            .to_string(),
+        framed(
+            r#"{"jsonrpc": "2.0", "id": "s", "method": "stress", "params": {"words": ["кот"]}}"#,
+        ),
+        "{".to_string(),
+        r#"{"jsonrpc": "2.0", "id": 2, "method": "rhyme"}"#.to_string(),
+        r#"{"jsonrpc": "2.0", "method": "find", "params": {"query": "ко'т"}}"#.to_string(),
+        r#"{"jsonrpc": "2.0", "id": 3, "method": "measure", "params": {"to_find": "ко'т"}}"#
+            .to_string(),
+        r#"{"jsonrpc": "2.0", "id": 4, "method": "shutdown"}"#.to_string(),
+        r#"{"jsonrpc": "2.0", "method": "exit"}"#.to_string(),
+        r#"{"jsonrpc": "2.0", "id": 5, "method": "themes"}"#.to_string(),
+    ]
+    .join("\n");
+
+    let mut output = vec![];
+    run_rpc(&state, input.as_bytes(), &mut output, 1).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    let (before, after) = output.split_once("\r\n\r\n").unwrap();
+    let (before, length) = before.rsplit_once("Content-Length: ").unwrap();
+    let answers: Vec<Value> = serde_json::Deserializer::from_str(&format!("{}{}", before, after))
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+    let by_id = |id: Value| answers.iter().find(|a| a["id"] == id).unwrap();
+
+    assert_eq!(by_id(json!(1))["result"]["results"][0]["word"], "кот");
+    // the framed request is answered with headers
+    let stressed = by_id(json!("s"));
+    assert_eq!(length.parse(), Ok(stressed.to_string().len()));
+    assert_eq!(stressed["result"]["results"][0]["stressed"], "ко'т");
+    assert_eq!(by_id(Value::Null)["error"]["code"], PARSE_ERROR);
+    assert_eq!(by_id(json!(2))["error"]["code"], METHOD_NOT_FOUND);
+    assert_eq!(by_id(json!(3))["error"]["code"], SERVER_ERROR);
+    assert_eq!(by_id(json!(4))["result"], Value::Null);
+    // notifications are not answered, nothing is read after exit
+    assert_eq!(answers.len(), 6);
+
+    let poem = json!({"jsonrpc": "2.0", "id": 1, "method": "analyze_poem",
+        "params": {"text": "Вот кот,\nидти,\nа вот крот.\n\nИдти!"}});
+    let mut output = vec![];
+    run_rpc(&state, poem.to_string().as_bytes(), &mut output, 1).unwrap();
+    let answer: Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(answer["result"]["scheme"], "ABA B");
+    assert_eq!(answer["result"]["lines"][2]["rhymes_with"], 1);
+
+    // a cancelled request is answered at once and only once
+    let input = [
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "find", "params": {"query": "ко'т"}}"#,
+        r#"{"jsonrpc": "2.0", "method": "$/cancelRequest", "params": {"id": 1}}"#,
+        r#"{"jsonrpc": "2.0", "method": "$/cancelRequest", "params": {"id": 1}}"#,
+    ]
+    .join("\n");
+    let mut output = vec![];
+    run_rpc(&state, input.as_bytes(), &mut output, 1).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(output.lines().count(), 1);
+    let answer: Value = serde_json::from_str(output.trim()).unwrap();
+    assert!(answer["result"].is_object() || answer["error"]["code"] == REQUEST_CANCELLED);
+
+    // a panicking request is answered with an error and doesn't kill its worker
+    assert_eq!(
+        catch_panic(|| -> Result<(), String> { panic!("boom") }),
+        Err("Internal error: boom".to_string())
+    );
+}
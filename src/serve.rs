@@ -0,0 +1,343 @@
+/*
+Rust implementation of advanced ryhmes finder
+Copyright (C) 2022  Andrej Sitnikov (sitandr, andr-sitnikov@mail.ru)
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+Module with local HTTP/JSON server (`quickpoeter serve`, not in wasm builds), the dictionary is loaded once;
+the methods behind the endpoints are in the methods module:
+  GET  /health
+  POST /find        {"query": "сло'во", "theme", "rps", "top_n", "sound_play", "word_play", "settings"}
+  POST /measure     {"to_find": "сло'во", "measured": "осно'ва", "theme", "settings"}
+  POST /transcribe  {"words": ["сло'во"], "notation": "ipa"}
+  POST /stress      {"words": ["слово"]}
+  POST /analyze     {"word": "слово"}
+  POST /analyze_poem {"text": "...", "settings"}
+  GET  /themes, GET /themes/<name>
+Only "query", "to_find", "measured", "words", "word" and "text" are required. "settings" are overrides
+for this request only, like {"stresses.weight": 80}. Errors are {"error": "..."}.
+Not more than `jobs` requests are processed at once (others get 503 at once),
+a request that takes longer than the timeout gets 504, but it can't be interrupted,
+so it keeps its place among `jobs` until it finishes
+*/
+
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::api::theme_words;
+use crate::methods::{call, State};
+
+/// requests with longer bodies are refused
+const MAX_BODY: u64 = 1 << 20;
+
+/// «%D0%BB%D0%B5%D1%81» → «лес» (browsers encode non-ASCII paths); None if it is not valid UTF-8
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// method name and parameters for the HTTP request; Err is the status and the message
+fn route(method: &Method, path: &str, body: &str) -> Result<(&'static str, Value), (u16, String)> {
+    let body = || -> Result<Value, (u16, String)> {
+        match body.trim() {
+            "" => Ok(json!({})),
+            body => serde_json::from_str(body).map_err(|err| (400, format!("Bad JSON: {}", err))),
+        }
+    };
+    let path = path
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('/');
+    match (method, path) {
+        (Method::Get, "/health") => Ok(("health", json!({}))),
+        (Method::Post, "/find") => Ok(("find", body()?)),
+        (Method::Post, "/measure") => Ok(("measure", body()?)),
+        (Method::Post, "/transcribe") => Ok(("transcribe", body()?)),
+        (Method::Post, "/stress") => Ok(("stress", body()?)),
+        (Method::Post, "/analyze") => Ok(("analyze", body()?)),
+        (Method::Post, "/analyze_poem") => Ok(("analyze_poem", body()?)),
+        (Method::Get, "/themes") => Ok(("themes", json!({}))),
+        (Method::Get, p) if p.starts_with("/themes/") => {
+            let name = percent_decode(&p["/themes/".len()..])
+                .ok_or_else(|| (400, format!("Bad theme name in the path: {}", p)))?;
+            Ok(("theme", json!({ "name": name })))
+        }
+        (
+            _,
+            "/health" | "/themes" | "/find" | "/measure" | "/transcribe" | "/stress" | "/analyze"
+            | "/analyze_poem",
+        ) => {
+            Err((405, format!("Method {} is not allowed here", method)))
+        }
+        _ => Err((404, format!("Unknown path: {}", path))),
+    }
+}
+
+/// decreases the number of running requests even if the request panics
+struct Running(Arc<AtomicUsize>);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub struct HttpServer {
+    server: Arc<Server>,
+    state: Arc<State>,
+    jobs: usize,
+    timeout: Duration,
+    running: Arc<AtomicUsize>,
+    stopped: AtomicBool,
+}
+
+impl HttpServer {
+    pub fn bind(
+        addr: &str,
+        state: Arc<State>,
+        jobs: usize,
+        timeout: Duration,
+    ) -> Result<Self, String> {
+        let server =
+            Server::http(addr).map_err(|err| format!("Can't listen on {}: {}", addr, err))?;
+        Ok(HttpServer {
+            server: Arc::new(server),
+            state,
+            jobs: jobs.max(1),
+            timeout,
+            running: Arc::new(AtomicUsize::new(0)),
+            stopped: AtomicBool::new(false),
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    /// serves requests in `jobs` threads until `stop`
+    pub fn run(&self) {
+        thread::scope(|scope| {
+            for _ in 0..self.jobs {
+                scope.spawn(|| loop {
+                    match self.server.recv() {
+                        Ok(request) => self.respond(request),
+                        Err(_) if self.stopped.load(Ordering::SeqCst) => break,
+                        Err(err) => eprintln!("{}", err),
+                    }
+                });
+            }
+        });
+    }
+
+    #[allow(dead_code)]
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        for _ in 0..self.jobs {
+            self.server.unblock();
+        }
+    }
+
+    fn respond(&self, mut request: Request) {
+        let mut body = String::new();
+        let read = request
+            .as_reader()
+            .take(MAX_BODY + 1)
+            .read_to_string(&mut body);
+        let (status, value) = match read {
+            Err(err) => (400, json!({ "error": err.to_string() })),
+            Ok(_) if body.len() as u64 > MAX_BODY => (413, json!({ "error": "Too long request" })),
+            Ok(_) => match route(request.method(), request.url(), &body) {
+                Ok(("health", _)) => (200, json!({ "status": "ok" })),
+                Ok(("theme", params)) => {
+                    let name = params["name"].as_str().unwrap_or_default();
+                    match theme_words(&self.state.mf, name) {
+                        // the theme is a resource, so an unknown one is «not found», not a bad request
+                        Err(message) => (404, json!({ "error": message })),
+                        Ok(_) => self.call_limited("theme", params),
+                    }
+                }
+                Ok((method, params)) => self.call_limited(method, params),
+                Err((status, message)) => (status, json!({ "error": message })),
+            },
+        };
+        let response = Response::from_string(value.to_string())
+            .with_status_code(status)
+            .with_header(
+                Header::from_bytes("Content-Type", "application/json; charset=utf-8").unwrap(),
+            );
+        if let Err(err) = request.respond(response) {
+            eprintln!("{}", err);
+        }
+    }
+
+    /// the call in a separate thread, so that it can be abandoned after the timeout
+    fn call_limited(&self, method: &'static str, params: Value) -> (u16, Value) {
+        if self.running.fetch_add(1, Ordering::SeqCst) >= self.jobs {
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            return (503, json!({ "error": "Too many requests, try later" }));
+        }
+        let running = Running(self.running.clone());
+        let state = self.state.clone();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _running = running;
+            let _ = tx.send(call(&state, method, &params));
+        });
+        match rx.recv_timeout(self.timeout) {
+            Ok(Ok(value)) => (200, value),
+            Ok(Err(message)) => (400, json!({ "error": message })),
+            Err(mpsc::RecvTimeoutError::Timeout) => (504, json!({ "error": "Timed out" })),
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                (500, json!({ "error": "Internal error" }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn check_serve() {
+    use crate::finder::WordCollector;
+    use crate::reader::{GeneralSettings, MeanStrThemes, VECTOR_DIM};
+    use std::collections::HashMap;
+    use std::io::Write;
+    use std::net::TcpStream;
+    use std::path::PathBuf;
+
+    let words = [
+        ("кот", "с+ко'т+;а"),
+        ("рот", "с+ро'т+"),
+        ("крот", "с+кро'т+"),
+        ("дом", "с+до'м+"),
+    ];
+    let i2w = words.map(|(k, _)| k.to_string()).to_vec();
+    let zaliz = words
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let state = Arc::new(State {
+        wc: WordCollector::new(i2w, zaliz, vec![[0.0; VECTOR_DIM]; words.len()]),
+        mf: MeanStrThemes {
+            str_themes: HashMap::from([
+                ("Animals".to_string(), vec!["кот".to_string()]),
+                ("Звери".to_string(), vec!["крот".to_string()]),
+            ]),
+        },
+        gs: GeneralSettings::try_load_default(&PathBuf::from(".")).unwrap(),
+    });
+
+    let found = call(&state, "find", &json!({"query": "ко'т", "top_n": 2})).unwrap();
+    assert_eq!(found["results"].as_array().unwrap().len(), 2);
+    assert!(found["results"][0]["vowel"].is_number());
+    let found = call(&state, "find", &json!({"query": "ко'т", "rps": "с"})).unwrap();
+    assert_eq!(found["results"], json!([]));
+    assert!(call(&state, "find", &json!({"query": "ко'т", "top": 2})).is_err());
+    assert!(call(
+        &state,
+        "find",
+        &json!({"query": "ко'т", "settings": {"x.y": 1}})
+    )
+    .is_err());
+    let heavy =
+        json!({"to_find": "ко'т", "measured": "ро'т", "settings": {"stresses.weight": 1000}});
+    let light = json!({"to_find": "ко'т", "measured": "ро'т"});
+    assert!(
+        call(&state, "measure", &heavy).unwrap()["result"]["dist"].as_f64()
+            >= call(&state, "measure", &light).unwrap()["result"]["dist"].as_f64()
+    );
+    assert_eq!(
+        call(
+            &state,
+            "transcribe",
+            &json!({"words": ["ко'т"], "notation": "ipa"})
+        )
+        .unwrap()["results"][0]["word"],
+        "ко'т"
+    );
+    assert_eq!(
+        call(&state, "theme", &json!({"name": "Animals"})).unwrap()["words"],
+        json!(["кот"])
+    );
+
+    let server =
+        Arc::new(HttpServer::bind("127.0.0.1:0", state, 2, Duration::from_secs(10)).unwrap());
+    let addr = server.local_addr().unwrap();
+    let runner = {
+        let server = server.clone();
+        thread::spawn(move || server.run())
+    };
+    let request = |head: &str, body: &str| -> (u16, Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{}\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+            head,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response.split_once("\r\n\r\n").unwrap().1;
+        (status, serde_json::from_str(body).unwrap())
+    };
+
+    assert_eq!(
+        request("GET /health HTTP/1.1", ""),
+        (200, json!({"status": "ok"}))
+    );
+    let (status, found) = request("POST /find HTTP/1.1", r#"{"query": "ко'т", "top_n": 1}"#);
+    assert_eq!(
+        (status, found["results"][0]["word"].as_str()),
+        (200, Some("кот"))
+    );
+    assert_eq!(request("POST /find HTTP/1.1", "{").0, 400);
+    assert_eq!(request("POST /find HTTP/1.1", r#"{"query": "'"}"#).0, 400);
+    assert_eq!(request("GET /find HTTP/1.1", "").0, 405);
+    assert_eq!(request("GET /nothing HTTP/1.1", "").0, 404);
+    assert_eq!(
+        request("GET /themes HTTP/1.1", ""),
+        (200, json!({"themes": ["Animals", "Звери"]}))
+    );
+    assert_eq!(request("GET /themes/Plants HTTP/1.1", "").0, 404);
+    let (status, theme) = request("GET /themes/%D0%97%D0%B2%D0%B5%D1%80%D0%B8 HTTP/1.1", "");
+    assert_eq!((status, &theme["words"]), (200, &json!(["крот"])));
+    assert_eq!(request("GET /themes/%D0 HTTP/1.1", "").0, 400);
+    assert_eq!(request("GET /themes/%zz HTTP/1.1", "").0, 400);
+
+    server.stop();
+    runner.join().unwrap();
+}
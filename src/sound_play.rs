@@ -0,0 +1,193 @@
+/*
+Rust implementation of advanced ryhmes finder
+Copyright (C) 2022  Andrej Sitnikov (sitandr, andr-sitnikov@mail.ru)
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+Module that measures sound play inside lines (not rhymes): words are compared from the beginning,
+the i-th sound has weight 1/(i + shift)^pow, so the first sounds matter most
+  alliteration — consonants in order («бурый бык», a word starting with a vowel doesn't alliterate with consonants)
+  assonance    — the stressed vowel and the vowels in order («по'лдень до'лгий»)
+  consonance   — the same consonants anywhere in the word («ро'ща — шо'рох»)
+All distances are from 0 to 1 and are multiplied by `sound_play.weight`
+*/
+
+use serde::Deserialize;
+
+use crate::reader::{GeneralSettings, SoundPlaySettings};
+use crate::translator_ru::{Consonant, Vowel};
+use crate::translator_struct::{Accent, Consonantable, Phone, Voweable, Word};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SoundPlay {
+    Alliteration,
+    Assonance,
+    Consonance,
+}
+
+impl SoundPlay {
+    /// whether the distance is measured over vowels (otherwise — over consonants)
+    pub fn by_vowels(&self) -> bool {
+        matches!(self, SoundPlay::Assonance)
+    }
+}
+
+fn position_weight(i: usize, sett: &SoundPlaySettings) -> f32 {
+    1.0 / (i as f32 + sett.shift).powf(sett.pow)
+}
+
+/// consonants from the beginning; a word starting with a vowel starts with None
+fn onset_consonants(word: &Word) -> Vec<Option<&Consonant>> {
+    let mut res = vec![];
+    if matches!(word.phones().first(), Some(Phone::Vowel(_))) {
+        res.push(None);
+    }
+    res.extend(word.phones().iter().filter_map(|p| match p {
+        Phone::Consonant(c) => Some(Some(c)),
+        Phone::Vowel(_) => None,
+    }));
+    res
+}
+
+fn consonants(word: &Word) -> Vec<&Consonant> {
+    word.phones()
+        .iter()
+        .filter_map(|p| match p {
+            Phone::Consonant(c) => Some(c),
+            Phone::Vowel(_) => None,
+        })
+        .collect()
+}
+
+fn alliteration_dist(first: &Word, second: &Word, gs: &GeneralSettings) -> f32 {
+    let sett = &gs.sound_play;
+    let (first, second) = (onset_consonants(first), onset_consonants(second));
+    let len = first.len().max(second.len()).min(sett.depth);
+    let (mut dist, mut total) = (0.0, 0.0);
+    for i in 0..len {
+        let d = match (first.get(i), second.get(i)) {
+            (Some(None), Some(None)) => 0.0,
+            (Some(Some(c1)), Some(Some(c2))) => c1.distance(c2, &gs.alliteration.distance),
+            _ => 1.0,
+        };
+        dist += d * position_weight(i, sett);
+        total += position_weight(i, sett);
+    }
+    if total == 0.0 {
+        0.0
+    } else {
+        dist / total
+    }
+}
+
+/// primary stressed vowel or, if there is no primary stress, the secondary one
+fn stressed_vowel(word: &Word) -> Option<&Vowel> {
+    word.vowels()
+        .find(|v| matches!(v.accent, Accent::Primary))
+        .or_else(|| {
+            word.vowels()
+                .find(|v| matches!(v.accent, Accent::Secondary))
+        })
+}
+
+fn assonance_dist(first: &Word, second: &Word, gs: &GeneralSettings) -> f32 {
+    let sett = &gs.sound_play;
+    let vowel_dist = |v1: Option<&Vowel>, v2: Option<&Vowel>| match (v1, v2) {
+        (Some(v1), Some(v2)) => v1.distance(v2, &gs.stresses.distance),
+        _ => 1.0,
+    };
+    let mut dist = sett.stressed_vowel * vowel_dist(stressed_vowel(first), stressed_vowel(second));
+    let mut total = sett.stressed_vowel;
+
+    let (first, second): (Vec<&Vowel>, Vec<&Vowel>) =
+        (first.vowels().collect(), second.vowels().collect());
+    let len = first.len().max(second.len()).min(sett.depth);
+    for i in 0..len {
+        let d = vowel_dist(first.get(i).copied(), second.get(i).copied());
+        dist += d * position_weight(i, sett);
+        total += position_weight(i, sett);
+    }
+    if total == 0.0 {
+        0.0
+    } else {
+        dist / total
+    }
+}
+
+/// for every consonant — the closest one of the other word
+fn consonance_one_side(first: &[&Consonant], second: &[&Consonant], gs: &GeneralSettings) -> f32 {
+    let sett = &gs.sound_play;
+    let (mut dist, mut total) = (0.0, 0.0);
+    for (i, c1) in first.iter().enumerate() {
+        let d = second
+            .iter()
+            .map(|c2| c1.distance(c2, &gs.alliteration.distance))
+            .fold(1.0, f32::min);
+        dist += d * position_weight(i, sett);
+        total += position_weight(i, sett);
+    }
+    if total == 0.0 {
+        0.0
+    } else {
+        dist / total
+    }
+}
+
+fn consonance_dist(first: &Word, second: &Word, gs: &GeneralSettings) -> f32 {
+    let (first, second) = (consonants(first), consonants(second));
+    if first.is_empty() != second.is_empty() {
+        return 1.0;
+    }
+    (consonance_one_side(&first, &second, gs) + consonance_one_side(&second, &first, gs)) / 2.0
+}
+
+/// distance from 0 (the same beginning) to `sound_play.weight`
+pub fn sound_play_dist(first: &Word, second: &Word, kind: SoundPlay, gs: &GeneralSettings) -> f32 {
+    let dist = match kind {
+        SoundPlay::Alliteration => alliteration_dist(first, second, gs),
+        SoundPlay::Assonance => assonance_dist(first, second, gs),
+        SoundPlay::Consonance => consonance_dist(first, second, gs),
+    };
+    dist * gs.sound_play.weight
+}
+
+#[cfg(test)]
+#[test]
+fn check_sound_play() {
+    use std::path::PathBuf;
+    let gs = GeneralSettings::try_load_default(&PathBuf::from(".")).unwrap();
+    let dist = |kind, a: &str, b: &str| {
+        sound_play_dist(&Word::new(a, false), &Word::new(b, false), kind, &gs)
+    };
+
+    // the beginning is more important than the end
+    let a = SoundPlay::Alliteration;
+    assert_eq!(dist(a, "бу'рый", "бу'рый"), 0.0);
+    assert!(dist(a, "бу'рый", "бы'к") < dist(a, "бу'рый", "ку'рый"));
+    assert!(dist(a, "гро'м", "гра'д") < dist(a, "гро'м", "гла'д"));
+    assert!(dist(a, "гро'м", "кра'й") < dist(a, "гро'м", "шу'м"));
+    assert!(dist(a, "о'зеро", "у'зел") < dist(a, "о'зеро", "зе'ро"));
+
+    let s = SoundPlay::Assonance;
+    assert!(dist(s, "по'лдень", "до'лгий") < dist(s, "по'лдень", "ду'шный"));
+    assert!(dist(s, "доро'га", "моро'з") < dist(s, "доро'га", "дра'ма"));
+
+    let c = SoundPlay::Consonance;
+    assert!(dist(c, "ро'ща", "шо'рох") < dist(c, "ро'ща", "то'лк"));
+    assert!(dist(c, "ро'ща", "шо'рох") < dist(a, "ро'ща", "шо'рох"));
+    assert_eq!(dist(c, "и'а", "о'"), 0.0);
+    assert_eq!(dist(c, "и'а", "ко'т"), gs.sound_play.weight);
+}
@@ -0,0 +1,132 @@
+/*
+Rust implementation of advanced ryhmes finder
+Copyright (C) 2022  Andrej Sitnikov (sitandr, andr-sitnikov@mail.ru)
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+Module that splits consonant clusters between syllables by sonority
+
+Consonants between two vowels are divided so that the sonority rises towards the second vowel:
+stops (п т к ч) < fricatives (ф с ш х) < nasals (м н) < liquids (л р) < й.
+So sonorants before obstruents close the syllable (ка'р-та, ма'й-ка), obstruents before sonorants
+open the next one (ко'-бра, ви'-шня), equal consonants are split (ка'с-са, до'к-тор).
+Russian-specific: с, з, ш, ж before other obstruents start the syllable (се-стра', по'-здно, до'-ска).
+*/
+
+use crate::translator_ru::ALL_VOWELS;
+
+/// letters that are never syllable boundaries themselves: they stick to the previous letter
+const ATTACHED: [char; 4] = ['ь', 'ъ', '\'', '`'];
+
+/// sonority rank of a consonant in transcription notation (р л н м п т к с х ш ч ф й)
+pub fn sonority(c: char) -> u8 {
+    match c {
+        'п' | 'т' | 'к' | 'ч' => 1,
+        'ф' | 'с' | 'ш' | 'х' => 2,
+        'м' | 'н' => 3,
+        'л' | 'р' => 4,
+        'й' => 5,
+        _ => 0,
+    }
+}
+
+fn is_sibilant(c: char) -> bool {
+    c == 'с' || c == 'ш'
+}
+
+/// how many last consonants of the cluster between two vowels belong to the next syllable;
+/// consonants are in transcription notation
+pub fn onset_len(cluster: &[char]) -> usize {
+    let mut len = 0;
+    // the longest rising part
+    while len < cluster.len() {
+        let c = cluster[cluster.len() - 1 - len];
+        if len > 0 && sonority(c) >= sonority(cluster[cluster.len() - len]) {
+            break;
+        }
+        len += 1;
+    }
+    // ст, зд, шк, …
+    if len < cluster.len() && len > 0 {
+        let c = cluster[cluster.len() - 1 - len];
+        let next = cluster[cluster.len() - len];
+        if is_sibilant(c) && !is_sibilant(next) && sonority(next) <= 2 {
+            len += 1;
+        }
+    }
+    len
+}
+
+/// maps a letter to the consonant of transcription notation ignoring voice and softness
+fn letter2consonant(c: char) -> char {
+    match c {
+        'б' => 'п',
+        'в' => 'ф',
+        'г' => 'к',
+        'д' | 'ц' => 'т',
+        'ж' | 'щ' => 'ш',
+        'з' => 'с',
+        other => other,
+    }
+}
+
+/// splits written word into syllables (e.g. for hyphenation): «ла'сковый» → ["ла'", "ско", "вый"];
+/// ь, ъ and stress marks stay with the previous letter, a word without vowels is a single syllable
+pub fn hyphenate(word: &str) -> Vec<&str> {
+    // (byte offset, lowercase letter) of letters that are not attached to the previous one
+    let units: Vec<(usize, char)> = word
+        .char_indices()
+        .filter_map(|(i, c)| {
+            let c = c.to_lowercase().next().unwrap_or(c);
+            (i == 0 || !ATTACHED.contains(&c)).then_some((i, c))
+        })
+        .collect();
+    let vowels: Vec<usize> = (0..units.len())
+        .filter(|&i| ALL_VOWELS.contains(&units[i].1))
+        .collect();
+
+    let mut res = vec![];
+    let mut start = 0;
+    for pair in vowels.windows(2) {
+        let cluster: Vec<char> = units[pair[0] + 1..pair[1]]
+            .iter()
+            .map(|(_, c)| letter2consonant(*c))
+            .collect();
+        let boundary = units[pair[1] - onset_len(&cluster)].0;
+        res.push(&word[start..boundary]);
+        start = boundary;
+    }
+    res.push(&word[start..]);
+    res
+}
+
+#[cfg(test)]
+#[test]
+fn check_syllabifier() {
+    assert_eq!(hyphenate("ла'сковый"), vec!["ла'", "ско", "вый"]);
+    assert_eq!(hyphenate("сестра'"), vec!["се", "стра'"]);
+    assert_eq!(hyphenate("по'здно"), vec!["по'", "здно"]);
+    assert_eq!(hyphenate("ка'рта"), vec!["ка'р", "та"]);
+    assert_eq!(hyphenate("ма'йка"), vec!["ма'й", "ка"]);
+    assert_eq!(hyphenate("ко'бра"), vec!["ко'", "бра"]);
+    assert_eq!(hyphenate("ка'сса"), vec!["ка'с", "са"]);
+    assert_eq!(hyphenate("до'ктор"), vec!["до'к", "тор"]);
+    assert_eq!(hyphenate("Подъезд"), vec!["По", "дъезд"]);
+    assert_eq!(hyphenate("рыба'лька"), vec!["ры", "ба'ль", "ка"]);
+    assert_eq!(hyphenate("вьюга"), vec!["вью", "га"]);
+    assert_eq!(hyphenate("аи'ст"), vec!["а", "и'ст"]);
+    assert_eq!(hyphenate("вдр"), vec!["вдр"]);
+    assert_eq!(hyphenate(""), vec![""]);
+}
@@ -1 +1,23 @@
-// Oops…
+/// checks search quality on golden rhymes; needs the full dictionary, so run with
+/// `cargo test golden --release -- --ignored --nocapture`
+#[ignore]
+#[test]
+fn golden() {
+    use crate::api::{evaluate_golden, EvaluateArgs};
+    use crate::finder::WordCollector;
+    use crate::reader::{GeneralSettings, MeanStrThemes};
+    use std::path::PathBuf;
+
+    let dir = PathBuf::from(".");
+    let wc = WordCollector::load_default(&dir);
+    let mf = MeanStrThemes::load_default(&dir);
+    let gs = GeneralSettings::try_load_default(&dir).unwrap();
+    let args = EvaluateArgs {
+        golden: PathBuf::from("config/golden.yaml"),
+        k: None,
+    };
+
+    let report = evaluate_golden(&wc, &mf, &gs, &args).unwrap();
+    println!("{}", report);
+    assert!(report.passed(), "Search quality is below thresholds");
+}
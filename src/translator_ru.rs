@@ -17,10 +17,32 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 
 Module that keeps the specific knowledge of russian language rules. :)
+
+Transcription is written in postfix notation: every sound is a letter, modifiers follow it.
+
+Vowels: а о э и ы у (and pattern symbols: ! — any stressed syllable, + — any syllable);
+vowel modifiers: ' — primary stress, ` — secondary stress.
+
+Consonants: р л н м п т к с х ш ч ф й;
+consonant modifiers: * — voiced (п* = б, т* = д, к* = г, с* = з, ш* = ж, ф* = в, ч* = дж, х* = ɣ),
+^ — palatalized (soft). ц is written as тс, щ as ш^.
+
+Rules applied (in this order):
+1. spelling exceptions: «что», «конечно», «скучно» and others are read with ш
+2. unpronounceable clusters: со'лнце → сонце, се'рдце → серце, чу'вство → чуство, сч/жч → щ, тч → ч, -ться → -тса
+3. й before е, ё, ю, я at the start and after vowels/ь/ъ, otherwise they soften the previous consonant
+   (except always hard ж, ш, ц); и softens the previous consonant as well
+4. letters are replaced by sounds (б → п*, ь → ^, …)
+5. regressive voicing assimilation (сде'лать → з*де'лать, ло'дка → ло'тка) and final devoicing
+6. assimilative softening (ко'сть → ко'с^т^, ба'нтик → ба'н^т^ик)
+7. vowel reduction: unstressed о → а (akanye); unstressed е, and я before the stress, → и after soft consonants
+   (ikanye, except at the word end); е, а, и → ы after always hard ж, ш, ц
 */
 
 use std::fmt::Debug;
 
+use serde::Deserialize;
+
 use crate::{
     reader::{ConsonantDistanceSettings, VowelDistanceSettings},
     translator_struct::*,
@@ -49,39 +71,52 @@ macro_rules! J_MAP {
 }
 
 // const J_VOWELS: Vec<&char> = J_MAP.keys().collect();
-const SOFTABLE: [char; 10] = ['с', 'х', 'ф', 'к', 'т', 'п', 'р', 'л', 'н', 'м'];
-const REMOVING_VOICE: [char; 6] = ['п', 'ф', 'к', 'т', 'ш', 'с'];
-
-//0      Ф Т С   Ч
-//1 РЛНМ П     Ш
-//2        К Х
-const ALLITERATION: [(f32, f32); 12] = [
-    (0.0, 1.0), // р
-    (0.5, 1.0), // л
-    (1.0, 1.0), // н
-    (1.5, 1.0), // м
-    (3.0, 1.0), // п
-    (4.0, 0.0), // т
-    (4.0, 2.0), // к
-    (5.0, 0.0), // с
-    (5.0, 2.0), // х
-    (6.0, 1.0), // ш
-    (8.0, 0.0), // ч
-    (3.0, 0.0), // ф
+const SOFTABLE: [char; 15] = [
+    'с', 'х', 'ф', 'к', 'т', 'п', 'р', 'л', 'н', 'м', 'б', 'в', 'г', 'д', 'з',
 ];
-/*
- о
-  а  э
-        и
-у     ы
-*/
-const ASSONANSES: [(f32, f32); 6] = [
-    (5.0, 5.0), // а
-    (4.0, 6.0), // о
-    (7.0, 5.0), // э
-    (9.0, 4.0), // и
-    (7.0, 3.0), // ы
-    (3.0, 3.0), // у
+/// е, ё, ю, я don't soften them (always hard or always soft)
+const NOT_SOFTENED: [char; 5] = ['ж', 'ш', 'ц', 'ч', 'щ'];
+/// in the transcription (after letter replacement)
+const OBSTRUENTS: [char; 8] = ['п', 'ф', 'к', 'т', 'ш', 'с', 'ч', 'х'];
+const SOFTENED_BY_NEXT: [char; 3] = ['с', 'т', 'н'];
+const CONSONANT_MODIFIERS: [char; 2] = ['*', '^'];
+const STRESS_MARKS: [char; 2] = ['\'', '`'];
+const WORD_SEPARATORS: [char; 2] = [' ', '-'];
+
+/// words starting with these are read with «шт» instead of «чт»
+const SHT_WORDS: [&str; 1] = ["что"];
+const SHT_EXACT: [&str; 2] = ["нечто", "ничто"];
+/// the same for «шн»
+const SHN_WORDS: [&str; 7] = [
+    "скучн",
+    "яичниц",
+    "скворечн",
+    "прачечн",
+    "пустячн",
+    "горчичн",
+    "подсвечн",
+];
+const SHN_EXACT: [&str; 2] = ["конечно", "нарочно"];
+
+/// unpronounceable consonants and other clusters read differently
+const CLUSTERS: [(&str, &str); 17] = [
+    ("лнц", "нц"),   // солнце
+    ("рдц", "рц"),   // сердце
+    ("вств", "ств"), // чувство
+    ("стн", "сн"),   // честный
+    ("здн", "зн"),   // поздно
+    ("стл", "сл"),   // счастливый
+    ("ндск", "нск"), // голландский
+    ("нтск", "нск"), // гигантский
+    ("нтг", "нг"),   // рентген
+    ("здц", "сц"),   // уздцы
+    ("сч", "щ"),     // счастье
+    ("зч", "щ"),     // извозчик
+    ("жч", "щ"),     // мужчина
+    ("тч", "ч"),     // отчёт
+    ("дч", "ч"),     // молодчина
+    ("ться", "тса"), // учиться
+    ("тся", "тса"),  // учится
 ];
 
 /// important symbols used in code
@@ -107,6 +142,11 @@ pub struct Vowel {
 
 impl Vowel {
     pub const ALL: [char; 8] = ['а', 'о', 'э', 'и', 'ы', 'у', '!', '+'];
+
+    /// letter in transcription notation (without stress mark)
+    pub fn symbol(&self) -> char {
+        Self::ALL[self.letter as usize]
+    }
 }
 
 impl Voweable for Vowel {
@@ -118,20 +158,7 @@ impl Voweable for Vowel {
         {
             return 1.0;
         }
-        if cfg!(feature = "edit_distances") {
-            let (x1, y1) = sett.map[self.letter as usize];
-            let (x2, y2) = sett.map[other.letter as usize];
-
-            1.0_f32.min(
-                ((x1 - x2).abs().powf(sett.pow) + (y1 - y2).abs().powf(sett.pow))
-                    / sett.denominator,
-            )
-        } else {
-            let (x1, y1) = ASSONANSES[self.letter as usize];
-            let (x2, y2) = ASSONANSES[other.letter as usize];
-
-            1.0_f32.min(((x1 - x2).abs().powf(0.5) + (y1 - y2).abs().powf(0.5)) / 3.0)
-        }
+        sett.get(self.letter, other.letter)
     }
 
     fn accent(&self) -> Accent {
@@ -144,7 +171,7 @@ impl Debug for Vowel {
         write!(
             f,
             "{}{}",
-            Self::ALL[self.letter as usize],
+            self.symbol(),
             match self.accent {
                 Accent::Primary => "\'",
                 Accent::Secondary => "`",
@@ -165,6 +192,11 @@ impl Consonant {
     pub const ALL: [char; 13] = [
         'р', 'л', 'н', 'м', 'п', 'т', 'к', 'с', 'х', 'ш', 'ч', 'ф', 'й',
     ];
+
+    /// letter in transcription notation (without voice and softness marks)
+    pub fn symbol(&self) -> char {
+        Self::ALL[self.letter as usize]
+    }
 }
 
 impl Phonable for Vowel {
@@ -181,42 +213,12 @@ impl Phonable for Consonant {
 
 impl Consonantable for Consonant {
     fn distance(&self, other: &Self, sett: &ConsonantDistanceSettings) -> f32 {
-        if self.letter == other.letter {
-            return 0.0;
-        }
-        if self.letter != symbol_id!(й) && other.letter != symbol_id!(й) {
-            if cfg!(feature = "edit_distances") {
-                let (x1, y1) = sett.map[self.letter as usize];
-                let (x2, y2) = sett.map[other.letter as usize];
-                let mut d: f32 = 0.0;
-                if self.voiced == other.voiced {
-                    d += 0.5
-                }
-                if self.palatalized == other.palatalized {
-                    d += 0.5
-                };
-
-                1.0_f32.min(
-                    ((x1 - x2).abs().powf(sett.pow) + (y1 - y2).abs().powf(sett.pow) + d)
-                        / sett.denominator,
-                )
-            } else {
-                let (x1, y1) = ALLITERATION[self.letter as usize];
-                let (x2, y2) = ALLITERATION[other.letter as usize];
-                let mut d: f32 = 0.0;
-                if self.voiced == other.voiced {
-                    d += 0.5
-                }
-                if self.palatalized == other.palatalized {
-                    d += 0.5
-                };
-
-                1.0_f32.min(((x1 - x2).abs().powf(0.5) + (y1 - y2).abs().powf(0.5) + d) / 3.0)
-            }
-        } else {
-            // й + …? — already checked they are not equal
-            1.0
-        }
+        sett.get(
+            self.letter,
+            other.letter,
+            self.voiced != other.voiced,
+            self.palatalized != other.palatalized,
+        )
     }
 }
 
@@ -225,29 +227,173 @@ impl Debug for Consonant {
         write!(
             f,
             "{}{}{}",
-            Self::ALL[self.letter as usize],
+            self.symbol(),
             if self.voiced { "*" } else { "" },
             if self.palatalized { "^" } else { "" }
         )
     }
 }
 
+/// human-readable phonetic alphabets for auditing transcriptions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PhoneticAlphabet {
+    Ipa,
+    XSampa,
+}
+
+/// [ipa, x-sampa]; pattern symbols ! and + are rendered as V (any vowel)
+const VOWEL_SIGNS: [[&str; 2]; 8] = [
+    ["a", "a"],
+    ["o", "o"],
+    ["ɛ", "E"],
+    ["i", "i"],
+    ["ɨ", "1"],
+    ["u", "u"],
+    ["V", "V"],
+    ["V", "V"],
+];
+
+/// [[ipa, x-sampa] of voiceless, [ipa, x-sampa] of voiced], same order as `Consonant::ALL`
+const CONSONANT_SIGNS: [[[&str; 2]; 2]; 13] = [
+    [["r", "r"], ["r", "r"]],
+    [["ɫ", "5"], ["ɫ", "5"]],
+    [["n", "n"], ["n", "n"]],
+    [["m", "m"], ["m", "m"]],
+    [["p", "p"], ["b", "b"]],
+    [["t", "t"], ["d", "d"]],
+    [["k", "k"], ["g", "g"]],
+    [["s", "s"], ["z", "z"]],
+    [["x", "x"], ["ɣ", "G"]],
+    [["ʂ", "s`"], ["ʐ", "z`"]],
+    [["t͡ɕ", "ts\\"], ["d͡ʑ", "dz\\"]],
+    [["f", "f"], ["v", "v"]],
+    [["j", "j"], ["j", "j"]],
+];
+
+impl PhoneticAlphabet {
+    fn index(self) -> usize {
+        match self {
+            PhoneticAlphabet::Ipa => 0,
+            PhoneticAlphabet::XSampa => 1,
+        }
+    }
+
+    pub fn primary_stress(self) -> &'static str {
+        ["ˈ", "\""][self.index()]
+    }
+
+    pub fn secondary_stress(self) -> &'static str {
+        ["ˌ", "%"][self.index()]
+    }
+
+    fn palatalization(self) -> &'static str {
+        ["ʲ", "'"][self.index()]
+    }
+}
+
+impl Vowel {
+    /// stress is not rendered here as it belongs to the whole syllable (see `Word::render`)
+    pub fn render(&self, alphabet: PhoneticAlphabet) -> &'static str {
+        VOWEL_SIGNS[self.letter as usize][alphabet.index()]
+    }
+}
+
+impl Consonant {
+    pub fn render(&self, alphabet: PhoneticAlphabet) -> String {
+        let a = alphabet.index();
+        let sign = CONSONANT_SIGNS[self.letter as usize][self.voiced as usize][a];
+        match (self.symbol(), self.palatalized) {
+            // ч and й are always soft
+            ('ч', _) | ('й', _) | (_, false) => sign.to_string(),
+            ('л', true) => "l".to_string() + alphabet.palatalization(),
+            // щ is a long soft sibilant, not a soft ш
+            ('ш', true) => [["ɕː", "s\\:"], ["ʑː", "z\\:"]][self.voiced as usize][a].to_string(),
+            (_, true) => sign.to_string() + alphabet.palatalization(),
+        }
+    }
+}
+
+/// the way Russian is usually written: е instead of ё
+pub fn remove_yo(s: &str) -> String {
+    s.replace('ё', "е").replace('Ё', "Е")
+}
+
 pub fn transcript(w: &str, is_adj: bool) -> String {
     // returns a postfix transcript like "к*ара'ш"
     let mut w: Vec<char> = w.to_lowercase().chars().collect();
     if is_adj {
         replace_g_in_adj(&mut w);
     }
+    replace_exceptions(&mut w);
+    simplify_clusters(&mut w);
     j_replace(&mut w);
     i_soften(&mut w);
     letter_replace(&mut w);
-    remove_voice(&mut w);
+    assimilate_voice(&mut w);
+    assimilate_softness(&mut w);
     replace_oa(&mut w);
+    reduce_vowels(&mut w);
     w.into_iter().collect()
 }
 
+/// ranges of separate words (for phrases like "Митя Ляпин" or "кто-то")
+fn tokens(w: &[char]) -> Vec<std::ops::Range<usize>> {
+    let mut res = vec![];
+    let mut start = 0;
+    for (i, c) in w.iter().enumerate() {
+        if WORD_SEPARATORS.contains(c) {
+            res.push(start..i);
+            start = i + 1;
+        }
+    }
+    res.push(start..w.len());
+    res
+}
+
+fn replace_exceptions(w: &mut [char]) {
+    for range in tokens(w) {
+        let letters: String = w[range.clone()]
+            .iter()
+            .filter(|c| !STRESS_MARKS.contains(c))
+            .collect();
+
+        let starts = |list: &[&str]| list.iter().any(|e| letters.starts_with(e));
+        let second = if starts(&SHT_WORDS) || SHT_EXACT.contains(&&*letters) {
+            'т'
+        } else if starts(&SHN_WORDS) || SHN_EXACT.contains(&&*letters) {
+            'н'
+        } else {
+            continue;
+        };
+
+        if let Some(i) = range
+            .clone()
+            .find(|&i| w[i] == 'ч' && w.get(i + 1) == Some(&second))
+        {
+            w[i] = 'ш';
+        }
+    }
+}
+
+fn simplify_clusters(w: &mut Vec<char>) {
+    for (from, to) in CLUSTERS.iter() {
+        let from: Vec<char> = from.chars().collect();
+        let to: Vec<char> = to.chars().collect();
+        let mut i = 0;
+        while i + from.len() <= w.len() {
+            if w[i..i + from.len()] == from[..] {
+                w.splice(i..i + from.len(), to.iter().copied());
+                i += to.len();
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
 fn j_replace(w: &mut Vec<char>) {
-    if J_VOWELS.contains(&w[0]) {
+    if w.first().is_some_and(|c| J_VOWELS.contains(c)) {
         // енот — "й" в начале слова
         if w[0] == 'ё' {
             w.insert(1, '\'');
@@ -270,12 +416,13 @@ fn j_replace(w: &mut Vec<char>) {
             }
 
             if J_MARKERS.contains(&w[ind - 1]) {
-                w.insert(ind, 'й')
-            } else {
-                w.insert(ind, '^')
+                w.insert(ind, 'й');
+                offset += 1; // shift indexes
+            } else if !NOT_SOFTENED.contains(&w[ind - 1]) {
+                w.insert(ind, '^');
+                offset += 1;
             }
-            offset += 1; // shift indexes
-        } else if val == 'о' && w[i - 1] == 'ь' {
+        } else if val == 'о' && w[ind - 1] == 'ь' {
             // бульон
             w.insert(ind, 'й');
             offset += 1;
@@ -291,8 +438,8 @@ fn i_soften(w: &mut Vec<char>) {
         let ind = i + offset;
         let val = &w[ind];
 
-        if *val == 'и' && SOFTABLE.contains(&w[i - 1]) {
-            w.insert(i, '^');
+        if *val == 'и' && SOFTABLE.contains(&w[ind - 1]) {
+            w.insert(ind, '^');
             offset += 1; // shift indexes
         }
     }
@@ -325,58 +472,146 @@ fn letter_replace(w: &mut Vec<char>) {
     }
 }
 
-fn check_previous_voice(w: &mut Vec<char>, pos: usize) -> Option<usize> {
-    if pos <= 0 {
-        return None;
+/// index of the consonant letter right after the one at `pos` (only its modifiers can be between)
+fn next_consonant(w: &[char], pos: usize) -> Option<usize> {
+    let mut j = pos + 1;
+    while j < w.len() && CONSONANT_MODIFIERS.contains(&w[j]) {
+        j += 1;
     }
-    if w[pos - 1] == '*' {
-        return Some(pos - 1);
-    } else if pos >= 2 && w[pos - 1] == '^' && w[pos - 2] == '*' {
-        return Some(pos - 2);
-    }
-    None
+    (j < w.len() && Consonant::ALL.contains(&w[j])).then_some(j)
+}
+
+fn has_modifier(w: &[char], pos: usize, modifier: char) -> bool {
+    w[pos + 1..]
+        .iter()
+        .take_while(|c| CONSONANT_MODIFIERS.contains(c))
+        .any(|&c| c == modifier)
 }
 
-fn is_voiced(w: &mut Vec<char>, pos: usize) -> bool {
-    if pos >= w.len() - 2 {
-        return false;
+/// modifiers are always written as letter*^
+fn set_voice(w: &mut Vec<char>, pos: usize, voiced: bool) {
+    match (has_modifier(w, pos, '*'), voiced) {
+        (false, true) => w.insert(pos + 1, '*'),
+        (true, false) => {
+            w.remove(pos + 1);
+        }
+        _ => {}
     }
-    if w[pos + 1] == '*' {
-        return true;
-    } else if pos <= w.len() - 3 && w[pos + 1] == '^' && w[pos + 2] == '*' {
-        return true;
+}
+
+fn soften(w: &mut Vec<char>, pos: usize) {
+    if !has_modifier(w, pos, '^') {
+        let shift = if has_modifier(w, pos, '*') { 2 } else { 1 };
+        w.insert(pos + shift, '^');
     }
-    false
 }
 
-fn remove_voice(w: &mut Vec<char>) {
-    if let Some(pos) = check_previous_voice(w, w.len()) {
-        w.remove(pos);
+/// final devoicing and regressive assimilation: obstruent takes voice of the next obstruent
+/// (в doesn't voice the previous one, but is devoiced itself)
+fn assimilate_voice(w: &mut Vec<char>) {
+    for range in tokens(w).into_iter().rev() {
+        let mut next: Option<usize> = None; // next obstruent directly after the current one
+        for i in range.rev() {
+            if !OBSTRUENTS.contains(&w[i]) {
+                if !CONSONANT_MODIFIERS.contains(&w[i]) {
+                    next = None;
+                }
+                continue;
+            }
+            match next {
+                None if next_consonant(w, i).is_none() && is_token_end(w, i) => {
+                    set_voice(w, i, false)
+                }
+                Some(j) if next_consonant(w, i) == Some(j) => {
+                    if !has_modifier(w, j, '*') {
+                        set_voice(w, i, false)
+                    } else if w[j] != 'ф' {
+                        set_voice(w, i, true)
+                    }
+                }
+                _ => {}
+            }
+            next = Some(i);
+        }
     }
+}
 
-    let mut offset = 0;
-    for i in 1..w.len() {
-        // can't remove voice from nothing
-        let ind: usize = i.wrapping_add(offset);
-        let val = &w[ind];
+/// whether only modifiers are left till the end of the word
+fn is_token_end(w: &[char], pos: usize) -> bool {
+    w[pos + 1..]
+        .iter()
+        .take_while(|c| !WORD_SEPARATORS.contains(c))
+        .all(|c| CONSONANT_MODIFIERS.contains(c))
+}
 
-        if REMOVING_VOICE.contains(val) && (!is_voiced(w, ind)) {
-            if let Some(pos) = check_previous_voice(w, ind) {
-                w.remove(pos);
-                offset = offset.wrapping_sub(1);
+/// с, з, т, д, н become soft before soft т, д, с, з, н; н also before ч and щ
+fn assimilate_softness(w: &mut Vec<char>) {
+    for i in (0..w.len()).rev() {
+        if !SOFTENED_BY_NEXT.contains(&w[i]) {
+            continue;
+        }
+        if let Some(j) = next_consonant(w, i) {
+            let soft_next = SOFTENED_BY_NEXT.contains(&w[j]) && has_modifier(w, j, '^');
+            let n_before_ch =
+                w[i] == 'н' && (w[j] == 'ч' || (w[j] == 'ш' && has_modifier(w, j, '^')));
+            if soft_next || n_before_ch {
+                soften(w, i);
             }
         }
     }
 }
 
 fn replace_oa(w: &mut Vec<char>) {
-    for i in 0..w.len() - 1 {
+    for i in 0..w.len().saturating_sub(1) {
         if w[i] == 'о' && !(w[i + 1] == '\'' || w[i + 1] == '`') {
             w[i] = 'а';
         }
     }
 }
 
+/// ikanye and reduction after always hard consonants; unstressed vowels are known only if the word has a stress
+fn reduce_vowels(w: &mut [char]) {
+    for range in tokens(w) {
+        let has_stress = w[range.clone()]
+            .iter()
+            .any(|c| STRESS_MARKS.contains(c) || *c == '!');
+        let mut before_stress = true;
+        for i in range.clone() {
+            if w[i] == '\'' || w[i] == '!' {
+                before_stress = false;
+            }
+            if !matches!(w[i], 'э' | 'а' | 'и') || i == range.start {
+                continue;
+            }
+            let prev = w[i - 1];
+            let after_soft = prev == '^' || prev == 'й' || prev == 'ч';
+            let after_hard = prev == 'ш'
+                || (prev == '*' && i >= 2 && w[i - 2] == 'ш')
+                || (prev == 'с' && i >= 2 && w[i - 2] == 'т');
+
+            if w[i] == 'и' {
+                if after_hard {
+                    w[i] = 'ы'; // жизнь, цирк
+                }
+                continue;
+            }
+
+            let stressed = w.get(i + 1).is_some_and(|c| STRESS_MARKS.contains(c));
+            let word_end = i + 1 == range.end;
+            if !has_stress || stressed || word_end {
+                continue;
+            }
+            w[i] = match (w[i], after_soft, after_hard) {
+                ('э', true, _) => 'и',
+                ('а', true, _) if before_stress => 'и',
+                ('э', _, true) => 'ы',
+                ('а', _, true) if before_stress => 'ы',
+                (other, _, _) => other,
+            }
+        }
+    }
+}
+
 fn replace_g_in_adj(w: &mut Vec<char>) {
     if w.len() < 3 {
         return;
@@ -393,16 +628,16 @@ fn replace_g_in_adj(w: &mut Vec<char>) {
 fn j_replace_check() {
     assert_eq!(transcript("а'+", false), "а'+");
     assert_eq!(transcript("Я", false), "йа");
-    assert_eq!(transcript("Митя Ляпин", false), "м^ит^а л^апин");
-    assert_eq!(transcript("Митя Льяпин", false), "м^ит^а л^йапин");
-    assert_eq!(transcript("Енёня`яя", false), "йэн^о'н^а`йайа");
+    assert_eq!(transcript("Митя Ляпин", false), "м^ит^а л^ап^ин");
+    assert_eq!(transcript("Митя Льяпин", false), "м^ит^а л^йап^ин");
+    assert_eq!(transcript("Енёня`яя", false), "йин^о'н^а`йайа");
     assert_eq!(transcript("миньо'н", false), "м^ин^йо'н");
-    assert_eq!(transcript("бабузжка", false), "п*ап*ус*шка");
+    assert_eq!(transcript("бабузжка", false), "п*ап*усшка");
     assert_eq!(transcript("гро'б", false), "к*ро'п");
     assert_eq!(transcript("до'ждь", false), "т*о'шт^");
     assert_eq!(transcript("его", true), "йэф*о");
-    assert_eq!(transcript("кроманьо'нец", false), "краман^йо'н^этс");
-    assert_eq!(transcript("Ёжик", false), "йо'ш*ик");
+    assert_eq!(transcript("кроманьо'нец", false), "краман^йо'н^итс");
+    assert_eq!(transcript("Ёжик", false), "йо'ш*ык");
 }
 
 #[cfg(test)]
@@ -424,3 +659,156 @@ fn testing() {
     dbg!(mem::size_of::<Vowel>());
     dbg!(mem::size_of::<Consonant>());
 }
+
+#[cfg(test)]
+#[test]
+fn distance_tables() {
+    let gs = crate::reader::GeneralSettings::default();
+    let a = Vowel {
+        letter: 0,
+        accent: Accent::NoAccent,
+    };
+    let o = Vowel {
+        letter: 1,
+        accent: Accent::NoAccent,
+    };
+    // min(1, (1 + 1)/3)
+    assert!((a.distance(&o, &gs.stresses.distance) - 2.0 / 3.0).abs() < 1e-6);
+    assert_eq!(a.distance(&a, &gs.stresses.distance), 0.0);
+
+    let gs = gs
+        .with_overrides(&["stresses.distance.map.1=[5, 5]"])
+        .unwrap();
+    assert_eq!(a.distance(&o, &gs.stresses.distance), 0.0);
+
+    let r = Consonant {
+        letter: 0,
+        voiced: true,
+        palatalized: false,
+    };
+    let l = Consonant {
+        letter: 1,
+        voiced: true,
+        palatalized: true,
+    };
+    let mut sett = gs.alliteration.distance.clone();
+    let before = r.distance(&l, &sett);
+    sett.denominator *= 2.0;
+    sett.update_table();
+    assert_eq!(r.distance(&l, &sett), before / 2.0);
+
+    // т — т^ — д^: only features differ
+    let t = Consonant {
+        letter: 5,
+        voiced: false,
+        palatalized: false,
+    };
+    let t_soft = Consonant {
+        palatalized: true,
+        ..t.clone()
+    };
+    let d_soft = Consonant {
+        voiced: true,
+        ..t_soft.clone()
+    };
+    assert_eq!(t.distance(&t, &sett), 0.0);
+    let w = sett.weights.clone();
+    let k = 1.0 / sett.denominator;
+    assert!((t.distance(&t_soft, &sett) - w.palatalization * k).abs() < 1e-6);
+    assert!((t.distance(&d_soft, &sett) - (w.voicing + w.palatalization) * k).abs() < 1e-6);
+    assert!((t_soft.distance(&d_soft, &sett) - w.voicing * k).abs() < 1e-6);
+
+    sett.weights.voicing = 0.0;
+    sett.update_table();
+    assert_eq!(t_soft.distance(&d_soft, &sett), 0.0);
+}
+
+#[cfg(test)]
+#[test]
+fn transcription_rules() {
+    const CASES: [(&str, &str); 68] = [
+        // spelling exceptions
+        ("что'", "што'"),
+        ("что'бы", "што'п*ы"),
+        ("не'что", "н^э'што"),
+        ("ничто'", "н^ишто'"),
+        ("ничто'жный", "н^ичто'ш*ный"),
+        ("коне'чно", "кан^э'шно"),
+        ("коне'чный", "кан^э'чный"),
+        ("ску'чно", "ску'шно"),
+        ("яи'чница", "йии'шн^итса"),
+        ("по'чта", "по'чта"),
+        ("мечта'", "м^ичта'"),
+        // unpronounceable and merged clusters
+        ("со'лнце", "со'нтсэ"),
+        ("се'рдце", "с^э'ртсэ"),
+        ("чу'вство", "чу'стф*о"),
+        ("здра'вствуй", "с*т*ра'стф*уй"),
+        ("че'стный", "чэ'сный"),
+        ("по'здно", "по'с*но"),
+        ("счастли'вый", "ш^исл^и'ф*ый"),
+        ("голла'ндский", "к*алла'нск^ий"),
+        ("гига'нтский", "к*^ик*а'нск^ий"),
+        ("рентге'н", "р^инк*^э'н"),
+        ("сча'стье", "ш^а'с^т^йэ"),
+        ("изво'зчик", "ис*ф*о'ш^ик"),
+        ("мужчи'на", "муш^и'на"),
+        ("отчёт", "ачо'т"),
+        ("учи'ться", "учи'тса"),
+        ("у'чится", "у'читса"),
+        // voicing and devoicing
+        ("сде'лать", "с*^т*^э'лат^"),
+        ("вокза'л", "ф*ак*с*а'л"),
+        ("про'сьба", "про'с*^п*а"),
+        ("та'кже", "та'к*ш*э"),
+        ("ло'дка", "ло'тка"),
+        ("гро'б", "к*ро'п"),
+        ("гво'здь", "к*ф*о'с^т^"),
+        ("мо'зг", "мо'ск"),
+        ("бабузжка", "п*ап*усшка"),
+        ("вто'рник", "фто'рн^ик"),
+        ("сва'дьба", "сф*а'т*^п*а"),
+        ("отбо'р", "ат*п*о'р"),
+        // assimilative softening
+        ("ко'сть", "ко'с^т^"),
+        ("ба'нтик", "п*а'н^т^ик"),
+        ("сне'г", "с^н^э'к"),
+        ("ве'сть", "ф*^э'с^т^"),
+        ("ко'нчик", "ко'н^чик"),
+        ("ка'меньщик", "ка'м^ин^ш^ик"),
+        ("лесни'к", "л^ис^н^и'к"),
+        // vowel reduction
+        ("пята'к", "п^ита'к"),
+        ("язы'к", "йис*ы'к"),
+        ("ещё", "йиш^о'"),
+        ("ряды'", "р^ит*ы'"),
+        ("мо'ре", "мо'р^э"),
+        ("по'ле", "по'л^э"),
+        ("ве'тер", "ф*^э'т^ир"),
+        ("бере'за", "п*^ир^э'с*а"),
+        ("часы'", "чисы'"),
+        ("щади'ть", "ш^ит*^и'т^"),
+        ("колесо'", "кал^исо'"),
+        ("кроманьо'нец", "краман^йо'н^итс"),
+        ("Енёня`яя", "йин^о'н^а`йайа"),
+        // always hard ж, ш, ц
+        ("жена'", "ш*ына'"),
+        ("цена'", "тсына'"),
+        ("жале'ть", "ш*ыл^э'т^"),
+        ("шаги'", "шык*^и'"),
+        ("жи'знь", "ш*ы'с*^н^"),
+        ("цирк", "тсырк"),
+        ("шёпот", "шо'пат"),
+        ("Ёжик", "йо'ш*ык"),
+        // «*» with no letter before it
+        ("*эко'т", "*эко'т"),
+    ];
+    for (word, expected) in CASES {
+        assert_eq!(
+            transcript(word, false),
+            expected,
+            "transcription of «{}»",
+            word
+        );
+    }
+}
@@ -20,18 +20,19 @@ Module that creates and measures single words
 */
 
 use itertools::Itertools;
-use regex::Regex;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::ops::Range;
 
 use crate::reader::ConsonantDistanceSettings;
 use crate::reader::VowelDistanceSettings;
 use crate::reader::{
     AlliterationSettings, ConsonantStructureSettings, GeneralSettings, MiscSettings, StressSettings,
 };
+use crate::syllabifier::onset_len;
 use crate::translator_ru::symbol_id;
 use crate::translator_ru::ALL_VOWELS;
-use crate::translator_ru::{transcript, Consonant, Vowel};
+use crate::translator_ru::{transcript, Consonant, PhoneticAlphabet, Vowel};
 
 macro_rules! unwrap_enum {
     ($v:expr, $p: pat => $r: expr) => {
@@ -49,11 +50,33 @@ pub enum Accent {
     Secondary,
 }
 
+/// single sound of the transcription
 #[derive(Debug, Clone)]
-enum Phone {
+pub enum Phone {
     Vowel(Vowel),
     Consonant(Consonant),
-    None,
+}
+
+/// phone indexes of one syllable; onset and coda may be empty
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Syllable {
+    pub onset: Range<usize>,
+    pub nucleus: usize,
+    pub coda: Range<usize>,
+}
+
+/// rhyme ending by the position of the primary stress from the word end
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clausula {
+    /// stress on the last syllable (коне'ц)
+    Masculine,
+    /// on the penultimate (доро'га)
+    Feminine,
+    /// on the third from the end (ла'сковый)
+    Dactylic,
+    /// further; holds the number of syllables after the stressed one (вы'держанная — 4)
+    Hyperdactylic(usize),
 }
 
 pub trait Phonable {
@@ -100,11 +123,13 @@ pub struct Word {
 }
 
 impl Word {
+    /// creates word from the text with stress marks (`'` for primary, `` ` `` for secondary);
+    /// symbols that are neither sounds nor modifiers (spaces, hyphens) are ignored
     pub fn new(w: &str, is_adj: bool) -> Self {
         let src = w.replace(['\'', '`'], "");
         let w = transcript(w, is_adj);
         let mut phones = vec![];
-        let mut current: Phone = Phone::None; // we need to initialize somehow
+        let mut current: Option<Phone> = None;
         let mut only_real_letters = true;
 
         for l in w.chars() {
@@ -115,52 +140,49 @@ impl Word {
                 }
 
                 if Vowel::contains_char(&l) {
-                    Phone::Vowel(Vowel {
+                    Some(Phone::Vowel(Vowel {
                         letter: find_u8(l, Vowel::ALL.iter()),
                         accent: match l {
                             '!' => Accent::Primary,
                             _ => Accent::NoAccent,
                         },
-                    })
+                    }))
                 } else if Consonant::contains_char(&l) {
-                    Phone::Consonant(Consonant {
+                    Some(Phone::Consonant(Consonant {
                         letter: find_u8(l, Consonant::ALL.iter()),
                         voiced: false,
                         palatalized: false,
-                    })
+                    }))
                 } else {
-                    Phone::None // modificator
+                    None // modificator
                 }
             };
 
             match new_current {
-                Phone::None => match current {
-                    Phone::Vowel(ref mut v) => {
-                        if v.letter != symbol_id!(+) && v.letter != symbol_id!(!) {
-                            v.accent = match l {
-                                '\'' => Accent::Primary,
-                                '`' => Accent::Secondary,
-                                other => unreachable!("Bad vowel identifier {}", other),
-                            }
+                None => match current {
+                    Some(Phone::Vowel(ref mut v))
+                        if v.letter != symbol_id!(+) && v.letter != symbol_id!(!) =>
+                    {
+                        match l {
+                            '\'' => v.accent = Accent::Primary,
+                            '`' => v.accent = Accent::Secondary,
+                            _ => {}
                         }
                     }
-                    Phone::Consonant(ref mut c) => match l {
+                    Some(Phone::Consonant(ref mut c)) => match l {
                         '*' => c.voiced = true,
                         '^' => c.palatalized = true,
-                        other => unreachable!("Bad consonant identifier {}", other),
+                        _ => {}
                     },
-                    Phone::None => panic!("Modificator {} at the word start", l),
+                    _ => {}
                 },
-                Phone::Consonant(_) | Phone::Vowel(_) => {
-                    match current {
-                        Phone::None => {}
-                        _ => phones.push(current),
-                    }
+                Some(_) => {
+                    phones.extend(current);
                     current = new_current;
                 }
             }
         }
-        phones.push(current);
+        phones.extend(current);
         Self {
             phones,
             src,
@@ -185,11 +207,17 @@ impl Word {
     }
 
     fn has_cons_end(&self) -> bool {
-        matches!(self.phones.last().unwrap(), Phone::Consonant(_))
+        matches!(self.phones.last(), Some(Phone::Consonant(_)))
+    }
+
+    /// all sounds of the word in order
+    #[allow(dead_code)]
+    pub fn phones(&self) -> &[Phone] {
+        &self.phones
     }
 
     /// use .rev() to get back order
-    fn vowels(&self) -> impl DoubleEndedIterator<Item = &Vowel> {
+    pub fn vowels(&self) -> impl DoubleEndedIterator<Item = &Vowel> {
         self.phones.iter().filter_map(|p| match p {
             Phone::Vowel(v) => Some(v),
             _ => None,
@@ -216,7 +244,6 @@ impl Word {
                     len += 1;
                     None
                 }
-                _ => unreachable!(),
             }
         };
         let res = self
@@ -320,6 +347,9 @@ impl Word {
     }
 
     pub fn measure_struct_dist(&self, other: &Self, sett: &ConsonantStructureSettings) -> f32 {
+        if sett.by_syllables {
+            return self.measure_syllable_struct_dist(other, sett);
+        }
         let mut dist = 0.0;
 
         for (i, ((_, l1), (_, l2))) in self
@@ -334,6 +364,25 @@ impl Word {
         dist / (self.vowel_count as f32 + sett.asympt_shift).powf(sett.asympt) * sett.weight
     }
 
+    /// the same as `measure_struct_dist`, but compares onsets and codas of real syllables
+    /// instead of consonant groups between vowels
+    fn measure_syllable_struct_dist(&self, other: &Self, sett: &ConsonantStructureSettings) -> f32 {
+        let mut dist = 0.0;
+        let diff = |a: usize, b: usize| (a as f32 - b as f32).abs().powf(sett.pow);
+
+        for (i, (s1, s2)) in self
+            .syllables()
+            .iter()
+            .rev()
+            .zip(other.syllables().iter().rev())
+            .enumerate()
+        {
+            dist += (diff(s1.onset.len(), s2.onset.len()) + diff(s1.coda.len(), s2.coda.len()))
+                / (i as f32 + sett.shift_syll_ending).powf(sett.pow_syll_ending);
+        }
+        dist / (self.vowel_count as f32 + sett.asympt_shift).powf(sett.asympt) * sett.weight
+    }
+
     pub fn measure_misc(&self, other: &Self, sett: &MiscSettings) -> f32 {
         let mut dist = 0.0;
         if self.has_cons_end() != other.has_cons_end() {
@@ -358,63 +407,121 @@ impl Word {
             cons = first.measure_cons_dist(second, &gs.alliteration);
             structure = first.measure_struct_dist(second, &gs.consonant_structure);
         } else {
+            // words are filtered by the pattern, and its representative has the same vowels at the end
+            // as every matched word (see `Pattern::representative`)
             vowel = first.measure_vowel_dist(second, &gs.stresses);
-
-            if second.vowel_count != first.vowel_count {
-                misc = 100_000.0;
-            } else {
-                misc = 0.0;
-            }
+            misc = 0.0;
             cons = 0.0;
             structure = 0.0;
         }
         (misc, vowel, cons, structure)
     }
 
-    /// returns Some(Regexp) if the word is a pattern (e.g. +!ко)
-    /// otherwise returns None
-    /// Error if fails to create Regexp (e.g. incorrect syntax inside the word)
-    pub fn get_regexp(&self) -> Result<Option<Regex>, String> {
-        if self.only_real_letters {
-            return Ok(None);
-        }
-        let vowels: String = ALL_VOWELS.iter().collect();
-        let block = format!("([^{v}]*[{v}][^{v}]*)", v = &vowels);
-        let s = &self.src.replace('+', &block);
-        let s = s.replace('!', &block);
-        Regex::new(&format!("^{}$", s))
-            .map_err(|err| format!("{}", err))
-            .map(Some)
+    /// whether the word has syllable matchers like "+" or "!" (see pattern module)
+    pub fn has_wildcards(&self) -> bool {
+        !self.only_real_letters
     }
 
-    /// Returns position of primary stress and vec of positions of secondary
+    /// Returns position of primary stress and position of secondary (None if there are no such stresses)
     /// **IMPORTANT!** Returns the number of *vowel* in letter notation (starting from 0).
     /// It can't return absolute char just because it knows only about the sounds
-    /// (works correctly only with words with single primary and possible secondary stresses)
+    /// (if there are several stresses of one kind, returns the last one)
     #[allow(dead_code)]
-    pub fn get_stresses(&self) -> (usize, Option<usize>) {
-        let mut primary = usize::MAX;
+    pub fn get_stresses(&self) -> (Option<usize>, Option<usize>) {
+        let mut primary = None;
         let mut secondary = None;
         for (ind, vowel) in self.vowels().enumerate() {
             match vowel.accent {
-                Accent::Primary => primary = ind,
+                Accent::Primary => primary = Some(ind),
                 Accent::Secondary => secondary = Some(ind),
                 Accent::NoAccent => {}
             }
         }
-        assert_ne!(primary, usize::MAX);
         (primary, secondary)
     }
 
+    /// source with stress marks put back («все'», «всё» — ё is stressed by itself)
+    pub fn stressed_src(&self) -> String {
+        let (primary, secondary) = self.get_stresses();
+        let mut res = String::with_capacity(self.src.len() + 2);
+        let mut vowel_index = 0;
+        for c in self.src.chars() {
+            res.push(c);
+            if ALL_VOWELS.contains(&c.to_lowercase().next().unwrap_or(c)) || c == '+' || c == '!' {
+                if Some(vowel_index) == primary && c != 'ё' && c != '!' {
+                    res.push('\'');
+                } else if Some(vowel_index) == secondary {
+                    res.push('`');
+                }
+                vowel_index += 1;
+            }
+        }
+        res
+    }
+
     /// returns letter and position from the END of PRIMARY stress
-    pub fn get_primary_stress(&self) -> (u8, usize) {
-        for (ind, vowel) in self.vowels().rev().enumerate() {
-            match vowel.accent {
-                Accent::Primary => return (vowel.letter, ind),
-                _ => {}
+    pub fn get_primary_stress(&self) -> Option<(u8, usize)> {
+        self.vowels()
+            .rev()
+            .enumerate()
+            .find(|(_, vowel)| matches!(vowel.accent, Accent::Primary))
+            .map(|(ind, vowel)| (vowel.letter, ind))
+    }
+
+    /// splits the word into syllables, one for every vowel;
+    /// consonants between vowels are divided by sonority (see syllabifier module), the final ones go to the coda
+    /// (a word without vowels has no syllables)
+    pub fn syllables(&self) -> Vec<Syllable> {
+        let nuclei: Vec<usize> = self
+            .phones
+            .iter()
+            .positions(|p| matches!(p, Phone::Vowel(_)))
+            .collect();
+        let mut res: Vec<Syllable> = Vec::with_capacity(nuclei.len());
+        let mut onset_start = 0;
+        for (i, &nucleus) in nuclei.iter().enumerate() {
+            if let Some(previous) = res.last_mut() {
+                let cluster: Vec<char> = self.phones[previous.nucleus + 1..nucleus]
+                    .iter()
+                    .map(|p| unwrap_enum!(p, Phone::Consonant(c) => c.symbol()))
+                    .collect();
+                onset_start = nucleus - onset_len(&cluster);
+                previous.coda = previous.nucleus + 1..onset_start;
             }
+            let coda_end = if i + 1 == nuclei.len() {
+                self.phones.len()
+            } else {
+                nucleus + 1
+            };
+            res.push(Syllable {
+                onset: onset_start..nucleus,
+                nucleus,
+                coda: nucleus + 1..coda_end,
+            });
         }
-        unreachable!()
+        res
+    }
+
+    /// None if there is no primary stress
+    #[allow(dead_code)]
+    pub fn clausula(&self) -> Option<Clausula> {
+        self.get_primary_stress()
+            .map(|(_, from_end)| match from_end {
+                0 => Clausula::Masculine,
+                1 => Clausula::Feminine,
+                2 => Clausula::Dactylic,
+                n => Clausula::Hyperdactylic(n),
+            })
+    }
+
+    /// sounds after the vowel with primary stress (None if there is no primary stress)
+    #[allow(dead_code)]
+    pub fn post_tonic_tail(&self) -> Option<&[Phone]> {
+        let stressed = self
+            .phones
+            .iter()
+            .rposition(|p| matches!(p, Phone::Vowel(v) if matches!(v.accent, Accent::Primary)))?;
+        Some(&self.phones[stressed + 1..])
     }
 
     /// returns all stresses in word (useful if many possible, e.g. user input)
@@ -430,6 +537,62 @@ impl Word {
     }
 }
 
+impl Word {
+    /// renders the transcription in the given alphabet;
+    /// stress marks are put before the onset of the stressed syllable (see `syllables`)
+    pub fn render(&self, alphabet: PhoneticAlphabet) -> String {
+        let mut parts: Vec<String> = self
+            .phones
+            .iter()
+            .map(|p| match p {
+                Phone::Vowel(v) => v.render(alphabet).to_string(),
+                Phone::Consonant(c) => c.render(alphabet),
+            })
+            .collect();
+
+        for syllable in self.syllables() {
+            let mark = match &self.phones[syllable.nucleus] {
+                Phone::Vowel(v) => match v.accent {
+                    Accent::Primary => alphabet.primary_stress(),
+                    Accent::Secondary => alphabet.secondary_stress(),
+                    Accent::NoAccent => continue,
+                },
+                _ => continue,
+            };
+            parts[syllable.onset.start].insert_str(0, mark);
+        }
+        parts.concat()
+    }
+
+    /// transcription in the inner notation: «сло'ва», «п*ра'фта»
+    pub fn transcription(&self) -> String {
+        phones2string(&self.phones)
+    }
+
+    /// broad IPA transcription like «xaraˈʂo» (reduction is the one of `transcript`)
+    #[allow(dead_code)]
+    pub fn to_ipa(&self) -> String {
+        self.render(PhoneticAlphabet::Ipa)
+    }
+
+    /// the same as `to_ipa`, but in ASCII-only X-SAMPA
+    #[allow(dead_code)]
+    pub fn to_xsampa(&self) -> String {
+        self.render(PhoneticAlphabet::XSampa)
+    }
+}
+
+/// sounds in the inner notation (* is voiced, ^ is palatalized, ' and ` are stresses)
+pub fn phones2string(phones: &[Phone]) -> String {
+    phones
+        .iter()
+        .map(|p| match p {
+            Phone::Vowel(v) => format!("{:?}", v),
+            Phone::Consonant(c) => format!("{:?}", c),
+        })
+        .collect()
+}
+
 /// just finds index of element in an Iterator; panics if fails
 pub fn find_u8<'a, T, I>(elem: T, mut array: I) -> u8
 where
@@ -449,7 +612,7 @@ impl Display for Word {
 #[test]
 fn measure_struct() {
     use std::path::PathBuf;
-    let gs = GeneralSettings::load_default(&PathBuf::from("."));
+    let gs = GeneralSettings::try_load_default(&PathBuf::from(".")).unwrap();
     let w = Word::new("приве'т", false);
     let w1 = Word::new("уже'", false);
     let w2 = Word::new("мише'нь", false);
@@ -460,9 +623,29 @@ fn measure_struct() {
 #[cfg(test)]
 #[test]
 fn check_stress() {
-    assert_eq!(Word::new("ещё", false).get_stresses().0, 1);
-    assert_eq!(Word::new("лома'ть", false).get_stresses().0, 1);
-    assert_eq!(Word::new("ско'лько", false).get_stresses().0, 0);
+    assert_eq!(Word::new("ещё", false).get_stresses().0, Some(1));
+    assert_eq!(Word::new("лома'ть", false).get_stresses().0, Some(1));
+    assert_eq!(Word::new("ско'лько", false).get_stresses().0, Some(0));
+    assert_eq!(
+        Word::new("во`доро'д", false).get_stresses(),
+        (Some(2), Some(0))
+    );
+    assert_eq!(
+        Word::new("доро'га", false).get_primary_stress(),
+        Some((1, 1))
+    );
+
+    let stressless = Word::new("вода", false);
+    assert_eq!(stressless.get_stresses(), (None, None));
+    assert_eq!(stressless.get_primary_stress(), None);
+    assert_eq!(stressless.clausula(), None);
+    assert!(stressless.post_tonic_tail().is_none());
+    assert!(Word::new("", false).syllables().is_empty());
+
+    assert_eq!(Word::new("все'", false).stressed_src(), "все'");
+    assert_eq!(Word::new("всё", false).stressed_src(), "всё");
+    assert_eq!(Word::new("во`доро'д", false).stressed_src(), "во`доро'д");
+    assert_eq!(Word::new("+!ко", false).stressed_src(), "+!ко");
 }
 
 #[cfg(test)]
@@ -481,3 +664,74 @@ fn check_consonant_iterator() {
     assert_eq!(iter.next(), Some((0, 0)));
     assert_eq!(iter.next(), None);
 }
+
+#[cfg(test)]
+#[test]
+fn check_ipa() {
+    assert_eq!(Word::new("хорошо'", false).to_ipa(), "xaraˈʂo");
+    assert_eq!(Word::new("хорошо'", false).to_xsampa(), "xara\"s`o");
+    assert_eq!(Word::new("ещё", false).to_ipa(), "jiˈɕːo");
+    assert_eq!(Word::new("ещё", false).to_xsampa(), "ji\"s\\:o");
+    assert_eq!(Word::new("лю'бят", false).to_ipa(), "ˈlʲubʲat");
+    assert_eq!(Word::new("ча'сть", false).to_ipa(), "ˈt͡ɕasʲtʲ");
+    assert_eq!(Word::new("во`доро'д", false).to_ipa(), "ˌvodaˈrot");
+    assert_eq!(Word::new("а'+", false).to_ipa(), "ˈaV");
+    assert_eq!(Word::new("хле'б", false).to_ipa(), "ˈxlʲɛp");
+    assert_eq!(Word::new("сто'л", false).to_ipa(), "ˈstoɫ");
+    assert_eq!(Word::new("спла'в", false).to_xsampa(), "\"sp5af");
+    assert_eq!(Word::new("доска'", false).to_ipa(), "daˈska");
+}
+
+#[cfg(test)]
+#[test]
+fn check_phonetic_api() {
+    let w = Word::new("ла'сковый", false); // л а' с к а в ы й
+    assert_eq!(w.phones().len(), 8);
+    assert_eq!(
+        w.syllables(),
+        vec![
+            Syllable {
+                onset: 0..1,
+                nucleus: 1,
+                coda: 2..2
+            },
+            Syllable {
+                onset: 2..4,
+                nucleus: 4,
+                coda: 5..5
+            },
+            Syllable {
+                onset: 5..6,
+                nucleus: 6,
+                coda: 7..8
+            },
+        ]
+    );
+    assert_eq!(w.clausula(), Some(Clausula::Dactylic));
+    assert_eq!(w.post_tonic_tail().unwrap().len(), 6);
+
+    assert_eq!(
+        Word::new("коне'ц", false).clausula(),
+        Some(Clausula::Masculine)
+    );
+    assert_eq!(
+        Word::new("коне'ц", false).post_tonic_tail().unwrap().len(),
+        2
+    );
+    assert_eq!(
+        Word::new("доро'га", false).clausula(),
+        Some(Clausula::Feminine)
+    );
+    assert_eq!(
+        Word::new("вы'держанная", false).clausula(),
+        Some(Clausula::Hyperdactylic(4))
+    );
+
+    let syllables = Word::new("узлы'", false).syllables(); // у з л ы'
+    assert_eq!(syllables[0].onset, 0..0);
+    assert_eq!(syllables[1].onset, 1..3);
+
+    let syllables = Word::new("ка'рта", false).syllables(); // к а' р т а
+    assert_eq!(syllables[0].coda, 2..3);
+    assert_eq!(syllables[1].onset, 3..4);
+}
@@ -0,0 +1,376 @@
+/*
+Rust implementation of advanced ryhmes finder
+Copyright (C) 2022  Andrej Sitnikov (sitandr, andr-sitnikov@mail.ru)
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+Module that tunes settings weights from labelled rhyme pairs
+(derivative-free Nelder–Mead over a ranking loss)
+*/
+
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+
+use crate::reader::{get_yaml_path, set_yaml_path, GeneralSettings};
+use crate::translator_struct::Word;
+
+/// fields tuned if nothing else is specified; only ones that `Word::measure_distance` uses
+pub const DEFAULT_TUNED: [&str; 17] = [
+    "misc.same_cons_end",
+    "misc.length_diff_fine",
+    "stresses.k_not_strict_stress",
+    "stresses.k_strict_stress",
+    "stresses.pow_syll_ending",
+    "stresses.asympt",
+    "stresses.weight",
+    "consonant_structure.pow",
+    "consonant_structure.weight",
+    "alliteration.pow_coord_delta",
+    "alliteration.permutations",
+    "alliteration.weight",
+    "alliteration.asympt",
+    "alliteration.distance.weights.place",
+    "alliteration.distance.weights.manner",
+    "alliteration.distance.weights.voicing",
+    "alliteration.distance.weights.palatalization",
+];
+
+/// the bigger, the closer loss is to "share of wrong orders"
+const SHARPNESS: f32 = 10.0;
+/// parameters are changed at most this number of times in any direction
+/// (otherwise the optimizer just makes one of components huge)
+const MAX_FACTOR: f32 = 10.0;
+/// small fine for going away from the base settings, so that useless parameters stay the same
+const REGULARIZATION: f32 = 1e-3;
+
+/// file format:
+/// ```yaml
+/// good: # for each first word good pairs should be closer than bad ones
+///   - [сло'во, осно'ва]
+/// bad:
+///   - [сло'во, сто'л]
+/// ranked: # first is the query, then rhymes from best to worst
+///   - [глазу'нья, шалу'нья, колду'нья, ка'ша]
+/// ```
+#[derive(Deserialize, Default)]
+pub struct RawTuningSet {
+    #[serde(default)]
+    pub good: Vec<(String, String)>,
+    #[serde(default)]
+    pub bad: Vec<(String, String)>,
+    #[serde(default)]
+    pub ranked: Vec<Vec<String>>,
+}
+
+impl RawTuningSet {
+    pub fn words(&self) -> impl Iterator<Item = &String> {
+        self.good
+            .iter()
+            .chain(self.bad.iter())
+            .flat_map(|(a, b)| [a, b])
+            .chain(self.ranked.iter().flatten())
+    }
+}
+
+/// (query, better, worse): query should be closer to better than to worse
+pub struct TuningSet {
+    constraints: Vec<(Word, Word, Word)>,
+}
+
+impl TuningSet {
+    /// `to_word` converts strings to words (e.g. `|s| string2word(wc, s)`)
+    pub fn new<F>(raw: &RawTuningSet, to_word: F) -> Result<Self, String>
+    where
+        F: Fn(&str) -> Result<Word, String>,
+    {
+        let word = |s: &String| to_word(s).map_err(|err| format!("«{}»: {}", s, err));
+        let mut constraints = vec![];
+
+        for (query, good) in raw.good.iter() {
+            for (_, bad) in raw.bad.iter().filter(|(q, _)| q == query) {
+                constraints.push((word(query)?, word(good)?, word(bad)?));
+            }
+        }
+        for list in raw.ranked.iter() {
+            let query = word(list.first().ok_or("Empty ranked list")?)?;
+            let words = list[1..].iter().map(word).collect::<Result<Vec<_>, _>>()?;
+            for (i, better) in words.iter().enumerate() {
+                for worse in words[i + 1..].iter() {
+                    constraints.push((query.clone(), better.clone(), worse.clone()));
+                }
+            }
+        }
+
+        if constraints.is_empty() {
+            return Err("No comparisons in the tuning set (good pairs need bad ones with the same first word)".to_string());
+        }
+        Ok(TuningSet { constraints })
+    }
+
+    pub fn len(&self) -> usize {
+        self.constraints.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.constraints.is_empty()
+    }
+
+    /// smooth ranking loss in [0, ln 2·…]; scale-independent (uses relative difference of distances)
+    pub fn loss(&self, gs: &GeneralSettings) -> f32 {
+        let mut loss = 0.0;
+        for (query, better, worse) in self.constraints.iter() {
+            let (b, w) = (distance(query, better, gs), distance(query, worse, gs));
+            let x = SHARPNESS * (b - w) / (b + w + f32::EPSILON);
+            // softplus
+            loss += x.max(0.0) + (-x.abs()).exp().ln_1p();
+        }
+        loss / self.constraints.len() as f32
+    }
+
+    /// share of comparisons ordered correctly
+    pub fn accuracy(&self, gs: &GeneralSettings) -> f32 {
+        let correct = self
+            .constraints
+            .iter()
+            .filter(|(q, b, w)| distance(q, b, gs) < distance(q, w, gs))
+            .count();
+        correct as f32 / self.constraints.len() as f32
+    }
+}
+
+fn distance(first: &Word, second: &Word, gs: &GeneralSettings) -> f32 {
+    let (misc, vowel, cons, structure) = first.measure_distance(second, gs);
+    misc + vowel + cons + structure
+}
+
+pub struct TuningResult {
+    #[allow(dead_code)]
+    pub settings: GeneralSettings,
+    /// only the tuned fields; can be saved to config/profiles
+    pub profile: Value,
+    pub loss_before: f32,
+    pub loss_after: f32,
+    pub accuracy_before: f32,
+    pub accuracy_after: f32,
+}
+
+/// every parameter is multiplied by exp(x), so signs are kept and zero parameters stay zero
+pub struct Tuner<'a> {
+    set: &'a TuningSet,
+    tree: Value,
+    params: Vec<String>,
+    initial: Vec<f32>,
+}
+
+impl<'a> Tuner<'a> {
+    pub fn new<S>(set: &'a TuningSet, base: &GeneralSettings, params: &[S]) -> Result<Self, String>
+    where
+        S: AsRef<str>,
+    {
+        let tree = serde_yaml::to_value(base).map_err(|err| err.to_string())?;
+        let params: Vec<String> = params.iter().map(|p| p.as_ref().to_owned()).collect();
+        let initial = params
+            .iter()
+            .map(|p| {
+                get_yaml_path(&tree, p)?
+                    .as_f64()
+                    .map(|v| v as f32)
+                    .ok_or(format!("Setting {} is not a number", p))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Tuner {
+            set,
+            tree,
+            params,
+            initial,
+        })
+    }
+
+    fn tree(&self, x: &[f32]) -> Value {
+        let mut tree = self.tree.clone();
+        for ((path, v), k) in self.params.iter().zip(self.initial.iter()).zip(x) {
+            // through the string to keep short f32 representation in YAML
+            let value: f64 = (v * k.exp()).to_string().parse().expect("Always a number");
+            set_yaml_path(&mut tree, path, Value::from(value)).expect("Checked in new");
+        }
+        tree
+    }
+
+    /// None if settings became invalid
+    fn settings(&self, x: &[f32]) -> Option<GeneralSettings> {
+        serde_yaml::from_value::<GeneralSettings>(self.tree(x))
+            .ok()?
+            .validated()
+            .ok()
+    }
+
+    fn loss(&self, x: &[f32]) -> f32 {
+        if x.iter().any(|k| k.abs() > MAX_FACTOR.ln()) {
+            return f32::INFINITY;
+        }
+        let fine: f32 = x.iter().map(|k| k * k).sum::<f32>() * REGULARIZATION;
+        self.settings(x)
+            .map_or(f32::INFINITY, |gs| self.set.loss(&gs) + fine)
+    }
+
+    /// only tuned fields; lists are written whole as profiles replace them entirely
+    fn profile(&self, x: &[f32]) -> Value {
+        let tree = self.tree(x);
+        let mut profile = Value::Mapping(Mapping::new());
+        for path in self.params.iter() {
+            let mut from = &tree;
+            let mut to = &mut profile;
+            for key in path.split('.') {
+                if let Value::Sequence(_) = from {
+                    break;
+                }
+                from = &from[key];
+                let map = to.as_mapping_mut().expect("Only mappings are created");
+                to = map.entry(Value::from(key)).or_insert_with(|| match from {
+                    Value::Mapping(_) => Value::Mapping(Mapping::new()),
+                    _ => Value::Null,
+                });
+            }
+            *to = from.clone();
+        }
+        profile
+    }
+
+    pub fn run(&self, iterations: usize) -> TuningResult {
+        let start = vec![0.0; self.params.len()];
+        let before = self.settings(&start).expect("Base settings are valid");
+        let (best, _) = nelder_mead(|x| self.loss(x), start, 0.5, iterations);
+        let after = self
+            .settings(&best)
+            .expect("Infinite loss is never the best");
+        TuningResult {
+            loss_before: self.set.loss(&before),
+            accuracy_before: self.set.accuracy(&before),
+            accuracy_after: self.set.accuracy(&after),
+            loss_after: self.set.loss(&after),
+            profile: self.profile(&best),
+            settings: after,
+        }
+    }
+}
+
+/// minimizes `f` starting from the simplex x0, x0 + step·eᵢ
+/// returns the best point and its value
+pub fn nelder_mead<F>(f: F, x0: Vec<f32>, step: f32, iterations: usize) -> (Vec<f32>, f32)
+where
+    F: Fn(&[f32]) -> f32,
+{
+    let n = x0.len();
+    let mut simplex: Vec<(Vec<f32>, f32)> = (0..=n)
+        .map(|i| {
+            let mut x = x0.clone();
+            if i > 0 {
+                x[i - 1] += step;
+            }
+            let v = f(&x);
+            (x, v)
+        })
+        .collect();
+
+    let towards = |from: &[f32], to: &[f32], k: f32| -> Vec<f32> {
+        from.iter().zip(to).map(|(a, b)| a + k * (b - a)).collect()
+    };
+
+    for _ in 0..iterations {
+        simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+        if (simplex[n].1 - simplex[0].1).abs() < 1e-7 {
+            break;
+        }
+
+        let mut centroid = vec![0.0; n];
+        for (x, _) in simplex[..n].iter() {
+            for (c, xi) in centroid.iter_mut().zip(x) {
+                *c += xi / n as f32;
+            }
+        }
+
+        let worst = simplex[n].clone();
+        let reflected = towards(&worst.0, &centroid, 2.0);
+        let fr = f(&reflected);
+
+        if fr < simplex[0].1 {
+            let expanded = towards(&worst.0, &centroid, 3.0);
+            let fe = f(&expanded);
+            simplex[n] = if fe < fr {
+                (expanded, fe)
+            } else {
+                (reflected, fr)
+            };
+        } else if fr < simplex[n - 1].1 {
+            simplex[n] = (reflected, fr);
+        } else {
+            let contracted = towards(&worst.0, &centroid, 0.5);
+            let fc = f(&contracted);
+            if fc < worst.1 {
+                simplex[n] = (contracted, fc);
+            } else {
+                // shrink to the best
+                let best = simplex[0].0.clone();
+                for (x, v) in simplex[1..].iter_mut() {
+                    *x = towards(&best, x, 0.5);
+                    *v = f(x);
+                }
+            }
+        }
+    }
+    simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+    simplex.swap_remove(0)
+}
+
+#[cfg(test)]
+#[test]
+fn check_nelder_mead() {
+    let (x, v) = nelder_mead(
+        |x| (x[0] - 1.0).powi(2) + 2.0 * (x[1] + 2.0).powi(2),
+        vec![0.0, 0.0],
+        0.5,
+        500,
+    );
+    assert!(v < 1e-4);
+    assert!((x[0] - 1.0).abs() < 1e-2 && (x[1] + 2.0).abs() < 1e-2);
+}
+
+#[cfg(test)]
+#[test]
+fn check_tuning() {
+    let raw: RawTuningSet = serde_yaml::from_str(
+        "
+        good: [[сло'во, осно'ва], [пра'вда, непра'вда]]
+        bad: [[сло'во, сла'ва], [пра'вда, ва'та]]
+        ranked: [[ло'жка, оно'жка, моро'шка, ла'мпа]]
+        ",
+    )
+    .unwrap();
+    let set = TuningSet::new(&raw, |s| Ok(Word::new(s, false))).unwrap();
+    assert_eq!(set.len(), 5);
+
+    let gs = GeneralSettings::default();
+    let tuner = Tuner::new(&set, &gs, &["stresses.weight", "alliteration.weight"]).unwrap();
+    let result = tuner.run(30);
+    assert!(result.loss_after <= result.loss_before);
+
+    let profile = &result.profile;
+    assert!(profile["stresses"]["weight"].is_f64());
+    assert!(profile["alliteration"]["weight"].is_f64());
+    assert!(profile.get("misc").is_none());
+
+    assert!(Tuner::new(&set, &gs, &["stresses.indexation"]).is_err());
+}
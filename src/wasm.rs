@@ -0,0 +1,196 @@
+/*
+Rust implementation of advanced ryhmes finder
+Copyright (C) 2022  Andrej Sitnikov (sitandr, andr-sitnikov@mail.ru)
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+Module with JS API for the browser (feature `wasm`), built with
+  cargo rustc --lib --release --target wasm32-unknown-unknown --features wasm --crate-type cdylib
+  wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/quickpoeter.wasm
+the dictionary, settings and themes are embedded into the binary, nothing is read from files
+and everything runs in the calling thread. The dictionary is the full one with meaning vectors
+(the same as `WordCollector::default`), there is no compact version for the browser yet,
+so the module is big and `new Rhymer()` is slow (from Rust, `Rhymer::from_state` takes a smaller one).
+  const rhymer = new Rhymer();
+  rhymer.find("ко'т", {theme: "Love", top_n: 10, settings: {"stresses.weight": 80}});
+  rhymer.measure("ко'т", "кро'т");
+  rhymer.transcribe(["ко'т"], "ipa");
+  rhymer.call("analyze_poem", {text: "..."});
+Parameters and results are the same as for the HTTP server (see the methods module),
+errors are thrown as Error
+*/
+
+use js_sys::JSON;
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::api::theme_names;
+use crate::finder::WordCollector;
+use crate::methods::{call, State};
+use crate::reader::{GeneralSettings, MeanStrThemes};
+
+fn from_js(value: JsValue) -> Result<Value, JsError> {
+    if value.is_undefined() || value.is_null() {
+        return Ok(json!({}));
+    }
+    let text: String = JSON::stringify(&value)
+        .map_err(|_| JsError::new("Parameters can't be converted to JSON"))?
+        .into();
+    serde_json::from_str(&text).map_err(|e| JsError::new(&e.to_string()))
+}
+
+fn to_js(value: &Value) -> Result<JsValue, JsError> {
+    JSON::parse(&value.to_string()).map_err(|_| JsError::new("Result is not JSON"))
+}
+
+/// `params` with `fields` added (they are more important)
+fn with_fields(params: JsValue, fields: Value) -> Result<Value, JsError> {
+    let mut params = from_js(params)?;
+    let object = params
+        .as_object_mut()
+        .ok_or_else(|| JsError::new("Options must be an object"))?;
+    if let Value::Object(fields) = fields {
+        object.extend(fields);
+    }
+    Ok(params)
+}
+
+#[wasm_bindgen]
+pub struct Rhymer {
+    state: State,
+}
+
+#[wasm_bindgen]
+impl Rhymer {
+    /// loads the embedded dictionary (takes a while)
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Rhymer {
+        Rhymer::from_state(State {
+            wc: WordCollector::default(),
+            mf: MeanStrThemes::default(),
+            gs: GeneralSettings::default(),
+        })
+    }
+
+    /// any method of the methods module by name
+    pub fn call(&self, method: &str, params: JsValue) -> Result<JsValue, JsError> {
+        let params = from_js(params)?;
+        let res = call(&self.state, method, &params).map_err(|s| JsError::new(&s))?;
+        to_js(&res)
+    }
+
+    /// rhymes as objects with all subdistances;
+    /// options are {theme, rps, top_n, sound_play, word_play, settings}
+    pub fn find(&self, query: &str, options: JsValue) -> Result<JsValue, JsError> {
+        let params = with_fields(options, json!({ "query": query }))?;
+        let res = call(&self.state, "find", &params).map_err(|s| JsError::new(&s))?;
+        to_js(&res["results"])
+    }
+
+    /// distance with all subdistances; options are {theme, settings}
+    pub fn measure(
+        &self,
+        to_find: &str,
+        measured: &str,
+        options: JsValue,
+    ) -> Result<JsValue, JsError> {
+        let fields = json!({ "to_find": to_find, "measured": measured });
+        let params = with_fields(options, fields)?;
+        let res = call(&self.state, "measure", &params).map_err(|s| JsError::new(&s))?;
+        to_js(&res["result"])
+    }
+
+    /// [{word, transcription}]; notation is "ipa", "x-sampa" or undefined for the inner one
+    pub fn transcribe(
+        &self,
+        words: Vec<String>,
+        notation: Option<String>,
+    ) -> Result<JsValue, JsError> {
+        let params = json!({ "words": words, "notation": notation });
+        let res = call(&self.state, "transcribe", &params).map_err(|s| JsError::new(&s))?;
+        to_js(&res["results"])
+    }
+
+    pub fn themes(&self) -> Vec<String> {
+        theme_names(&self.state.mf)
+            .into_iter()
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+impl Default for Rhymer {
+    fn default() -> Self {
+        Rhymer::new()
+    }
+}
+
+impl Rhymer {
+    /// with another dictionary or settings
+    pub fn from_state(state: State) -> Rhymer {
+        Rhymer { state }
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+#[wasm_bindgen_test::wasm_bindgen_test]
+fn check_wasm() {
+    use crate::reader::VECTOR_DIM;
+    use js_sys::Reflect;
+
+    let words = [
+        ("кот", "с+ко'т+;а"),
+        ("рот", "с+ро'т+"),
+        ("крот", "с+кро'т+"),
+        ("дом", "с+до'м+"),
+    ];
+    let i2w = words.map(|(k, _)| k.to_string()).to_vec();
+    let zaliz = words
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let rhymer = Rhymer::from_state(State {
+        wc: WordCollector::new(i2w, zaliz, vec![[0.0; VECTOR_DIM]; words.len()]),
+        mf: MeanStrThemes::default(),
+        gs: GeneralSettings::default(),
+    });
+    let get = |v: &JsValue, key: &str| Reflect::get(v, &key.into()).unwrap();
+
+    let options = JSON::parse(r#"{"top_n": 2}"#).unwrap();
+    let found = rhymer.find("ко'т", options).unwrap();
+    assert_eq!(get(&found, "length").as_f64(), Some(2.0));
+    assert_eq!(
+        get(&get(&found, "0"), "word").as_string().as_deref(),
+        Some("кот")
+    );
+
+    let heavy = JSON::parse(r#"{"settings": {"stresses.weight": 1000}}"#).unwrap();
+    let dist = |r: JsValue| get(&r, "dist").as_f64().unwrap();
+    assert!(
+        dist(rhymer.measure("ко'т", "ро'т", heavy).unwrap())
+            >= dist(rhymer.measure("ко'т", "ро'т", JsValue::UNDEFINED).unwrap())
+    );
+
+    let transcribed = rhymer.transcribe(vec!["кот".to_string()], Some("ipa".to_string()));
+    let word = get(&get(&transcribed.unwrap(), "0"), "word");
+    assert_eq!(word.as_string().as_deref(), Some("ко'т"));
+    let stressed = rhymer.call("stress", JSON::parse(r#"{"words": ["кот"]}"#).unwrap());
+    assert!(stressed.is_ok());
+    assert!(rhymer.themes().contains(&"Love".to_string()));
+
+    assert!(rhymer.find("'", JsValue::UNDEFINED).is_err());
+    assert!(rhymer.find("ко'т", JsValue::from(1)).is_err());
+    assert!(rhymer.call("rhyme", JsValue::NULL).is_err());
+}
@@ -0,0 +1,337 @@
+/*
+Rust implementation of advanced ryhmes finder
+Copyright (C) 2022  Andrej Sitnikov (sitandr, andr-sitnikov@mail.ru)
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+
+Module that finds word play by sounds:
+  anagrams   — the same sounds in other order (ко'т — то'к)
+  paronyms   — a few sounds differ, the stress is on the same syllable from the end (адреса'т — адреса'нт)
+  homophones — words or pairs of words that sound (almost) like the given ones (за бо'р — забо'р)
+Sounds are compared by edit distance: inserting or removing a sound costs 1,
+replacing — the distance between the sounds (see `stresses.distance` and `alliteration.distance`).
+All results are in the order of word popularity if distances are equal
+*/
+
+use std::collections::HashSet;
+use std::fmt::{Debug, Formatter};
+
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize};
+
+use crate::finder::WordCollector;
+use crate::reader::GeneralSettings;
+use crate::translator_struct::{Consonantable, Phone, Voweable, Word};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WordPlay {
+    Anagram,
+    Paronym,
+    Homophone,
+}
+
+/// one or several words found; the less `dist`, the better
+#[derive(Clone)]
+pub struct WordPlayResult<'a> {
+    pub dist: f32,
+    pub words: Vec<&'a Word>,
+}
+
+impl WordPlayResult<'_> {
+    pub fn src(&self) -> String {
+        self.words
+            .iter()
+            .map(|w| &*w.src)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl Debug for WordPlayResult<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "\n{:<13} — {}",
+            self.src(),
+            f32::round(self.dist * 1_000.0) / 1_000.0
+        )
+    }
+}
+
+impl Serialize for WordPlayResult<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut s = serializer.serialize_struct("WordPlayResult", 2)?;
+        s.serialize_field("dist", &self.dist)?;
+        s.serialize_field(
+            "words",
+            &self.words.iter().map(|w| &*w.src).collect::<Vec<_>>(),
+        )?;
+        s.end()
+    }
+}
+
+/// sound without stress; voice and softness are kept
+fn phone_key(p: &Phone) -> (bool, u8, bool, bool) {
+    match p {
+        Phone::Vowel(v) => (false, v.letter, false, false),
+        Phone::Consonant(c) => (true, c.letter, c.voiced, c.palatalized),
+    }
+}
+
+fn phone_keys(word: &Word) -> Vec<(bool, u8, bool, bool)> {
+    word.phones().iter().map(phone_key).collect()
+}
+
+fn sorted_phone_keys(word: &Word) -> Vec<(bool, u8, bool, bool)> {
+    let mut keys = phone_keys(word);
+    keys.sort();
+    keys
+}
+
+fn phone_cost(first: &Phone, second: &Phone, gs: &GeneralSettings) -> f32 {
+    match (first, second) {
+        (Phone::Vowel(v1), Phone::Vowel(v2)) => v1.distance(v2, &gs.stresses.distance),
+        (Phone::Consonant(c1), Phone::Consonant(c2)) => c1.distance(c2, &gs.alliteration.distance),
+        _ => 1.0,
+    }
+}
+
+/// edit distances from `word` to every prefix of `target` (the index is the prefix length)
+fn prefix_distances<'p>(
+    word: impl Iterator<Item = &'p Phone>,
+    target: &[&Phone],
+    gs: &GeneralSettings,
+) -> Vec<f32> {
+    let mut row: Vec<f32> = (0..=target.len()).map(|j| j as f32).collect();
+    for (i, p) in word.enumerate() {
+        let mut next = vec![i as f32 + 1.0; target.len() + 1];
+        for (j, t) in target.iter().enumerate() {
+            next[j + 1] = (row[j] + phone_cost(p, t, gs))
+                .min(row[j + 1] + 1.0)
+                .min(next[j] + 1.0);
+        }
+        row = next;
+    }
+    row
+}
+
+pub fn phone_edit_distance(first: &Word, second: &Word, gs: &GeneralSettings) -> f32 {
+    let target: Vec<&Phone> = second.phones().iter().collect();
+    prefix_distances(first.phones().iter(), &target, gs)[target.len()]
+}
+
+impl WordCollector {
+    /// words with the same sounds in other order
+    pub fn find_anagrams(&self, word: &Word, top_n: usize) -> Vec<WordPlayResult<'_>> {
+        let keys = phone_keys(word);
+        let sorted = sorted_phone_keys(word);
+        let own_group = self.get_forms(&word.src).copied();
+        let mut last_group = None;
+        let mut res = vec![];
+        for (i, w) in self.words().iter().enumerate() {
+            if res.len() >= top_n {
+                break;
+            }
+            let group = self.get_forms_by_word_index(&i).copied();
+            if group == own_group
+                || group == last_group
+                || w.get_phones_count() != word.get_phones_count()
+                || sorted_phone_keys(w) != sorted
+                || phone_keys(w) == keys
+            {
+                continue;
+            }
+            last_group = group;
+            res.push(WordPlayResult {
+                dist: 0.0,
+                words: vec![w],
+            });
+        }
+        res
+    }
+
+    /// words that differ in a few sounds (not more than `word_play.max_dist`) with the same stressed syllable;
+    /// the best form of every word
+    pub fn find_paronyms(
+        &self,
+        word: &Word,
+        gs: &GeneralSettings,
+        top_n: usize,
+    ) -> Vec<WordPlayResult<'_>> {
+        let max_dist = gs.word_play.max_dist;
+        let stress = word.get_primary_stress().map(|(_, position)| position);
+        let own_group = self.get_forms(&word.src).copied();
+        let mut res: Vec<(Option<usize>, WordPlayResult)> = vec![];
+        for (i, w) in self.words().iter().enumerate() {
+            let group = self.get_forms_by_word_index(&i).copied();
+            if group == own_group
+                || (w.get_phones_count() as f32 - word.get_phones_count() as f32).abs() > max_dist
+                || w.get_primary_stress().map(|(_, position)| position) != stress
+            {
+                continue;
+            }
+            let dist = phone_edit_distance(word, w, gs);
+            if dist == 0.0 || dist > max_dist {
+                continue;
+            }
+            match res.last_mut() {
+                Some((g, best)) if *g == group => {
+                    if dist < best.dist {
+                        *best = WordPlayResult {
+                            dist,
+                            words: vec![w],
+                        };
+                    }
+                }
+                _ => res.push((
+                    group,
+                    WordPlayResult {
+                        dist,
+                        words: vec![w],
+                    },
+                )),
+            }
+        }
+        let mut res: Vec<WordPlayResult> = res.into_iter().map(|(_, r)| r).collect();
+        res.sort_by(|a, b| a.dist.total_cmp(&b.dist));
+        res.truncate(top_n);
+        res
+    }
+
+    /// single words and pairs of words that sound like the phrase (or a single word) with
+    /// the distance not more than `word_play.max_dist`; the phrase itself is not returned
+    pub fn find_homophones(
+        &self,
+        phrase: &[Word],
+        gs: &GeneralSettings,
+        top_n: usize,
+    ) -> Vec<WordPlayResult<'_>> {
+        let max_dist = gs.word_play.max_dist;
+        let target: Vec<&Phone> = phrase.iter().flat_map(|w| w.phones()).collect();
+        let reversed: Vec<&Phone> = target.iter().rev().copied().collect();
+        let n = target.len();
+
+        // candidates for the whole phrase and for the left and the right parts split after k sounds
+        let mut whole = vec![];
+        let mut left: Vec<Vec<(f32, &Word)>> = vec![vec![]; n];
+        let mut right: Vec<Vec<(f32, &Word)>> = vec![vec![]; n];
+        for w in self.words().iter() {
+            if w.get_phones_count() as f32 > n as f32 + max_dist {
+                continue;
+            }
+            let prefix = prefix_distances(w.phones().iter(), &target, gs);
+            let suffix = prefix_distances(w.phones().iter().rev(), &reversed, gs);
+            for k in 1..n {
+                if prefix[k] <= max_dist {
+                    left[k].push((prefix[k], w));
+                }
+                if suffix[n - k] <= max_dist {
+                    right[k].push((suffix[n - k], w));
+                }
+            }
+            if prefix[n] <= max_dist {
+                whole.push(WordPlayResult {
+                    dist: prefix[n],
+                    words: vec![w],
+                });
+            }
+        }
+
+        let mut res = whole;
+        for k in 1..n {
+            left[k].sort_by(|a, b| a.0.total_cmp(&b.0));
+            right[k].sort_by(|a, b| a.0.total_cmp(&b.0));
+            for (dl, wl) in left[k].iter().take(top_n) {
+                for (dr, wr) in right[k].iter().take(top_n) {
+                    if dl + dr <= max_dist {
+                        res.push(WordPlayResult {
+                            dist: dl + dr,
+                            words: vec![wl, wr],
+                        });
+                    }
+                }
+            }
+        }
+        res.sort_by(|a, b| a.dist.total_cmp(&b.dist));
+
+        let source: Vec<&str> = phrase.iter().map(|w| &*w.src).collect();
+        let mut seen = HashSet::new();
+        res.retain(|r| {
+            let srcs: Vec<&str> = r.words.iter().map(|w| &*w.src).collect();
+            srcs != source && seen.insert(srcs)
+        });
+        res.truncate(top_n);
+        res
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn check_wordplay() {
+    use crate::reader::VECTOR_DIM;
+    use std::path::PathBuf;
+    let gs = GeneralSettings::try_load_default(&PathBuf::from(".")).unwrap();
+    let words = [
+        ("кот", "с+ко'т+;а"),
+        ("ток", "с+то'к+;а"),
+        ("код", "с+ко'д+"),
+        ("рот", "с+ро'т+"),
+        ("забор", "с+забо'р+"),
+        ("за", "предл+за'+"),
+        ("бор", "с+бо'р+"),
+        ("адресат", "с+адреса'т+"),
+        ("адресант", "с+адреса'нт+"),
+    ];
+    let i2w = words.map(|(k, _)| k.to_string()).to_vec();
+    let zaliz = words
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let wc = WordCollector::new(i2w, zaliz, vec![[0.0; VECTOR_DIM]; words.len()]);
+    let srcs = |res: Vec<WordPlayResult>| res.iter().map(|r| r.src()).collect::<Vec<_>>();
+
+    assert_eq!(
+        srcs(wc.find_anagrams(&Word::new("ко'т", false), 10)),
+        vec!["ток"]
+    );
+    assert_eq!(
+        srcs(wc.find_anagrams(&Word::new("ко'ты", false), 10)),
+        Vec::<String>::new()
+    );
+
+    assert_eq!(
+        srcs(wc.find_paronyms(&Word::new("адреса'т", false), &gs, 10)),
+        vec!["адресант"]
+    );
+    // код sounds the same as кот, so it is not a paronym
+    let paronyms = srcs(wc.find_paronyms(&Word::new("ко'т", false), &gs, 10));
+    assert!(paronyms.contains(&"рот".to_string()));
+    assert!(paronyms.contains(&"ток".to_string()));
+    assert!(!paronyms.contains(&"код".to_string()));
+    assert_eq!(
+        phone_edit_distance(&Word::new("ко'т", false), &Word::new("ко'д", false), &gs),
+        0.0
+    );
+
+    let phrase = [Word::new("за'", false), Word::new("бо'р", false)];
+    assert_eq!(srcs(wc.find_homophones(&phrase, &gs, 10))[0], "забор");
+    let homophones = srcs(wc.find_homophones(&[Word::new("забо'р", false)], &gs, 10));
+    assert_eq!(homophones[0], "за бор");
+    assert!(!homophones.contains(&"забор".to_string()));
+}